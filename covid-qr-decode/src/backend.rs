@@ -0,0 +1,64 @@
+use crate::error::{DecryptionError, SigningError};
+
+#[cfg(not(any(feature = "openssl", feature = "rust-crypto")))]
+compile_error!("at least one of the `openssl` or `rust-crypto` features must be enabled");
+
+#[cfg(feature = "openssl")]
+mod openssl;
+#[cfg(feature = "rust-crypto")]
+mod rust_crypto;
+
+/// Recovers messages signed with the RSA PKCS#1 v1.5 (type 1) scheme;
+/// OpenSSL calls this public key decryption
+pub trait SignatureRecovery: Send + Sync {
+    fn recover(&self, signature: &[u8]) -> Result<Vec<u8>, DecryptionError>;
+}
+
+/// Signs messages with the RSA PKCS#1 v1.5 (type 1) scheme, without a digest
+pub trait MessageSigner: Send + Sync {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, SigningError>;
+}
+
+/// Cryptography implementation; the available ones depend on the enabled cargo features
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CryptoBackend {
+    #[cfg(feature = "openssl")]
+    OpenSsl,
+    #[cfg(feature = "rust-crypto")]
+    RustCrypto,
+}
+
+impl Default for CryptoBackend {
+    // prefer OpenSSL when both are available
+    #[allow(unreachable_code)]
+    fn default() -> Self {
+        #[cfg(feature = "openssl")]
+        return CryptoBackend::OpenSsl;
+
+        #[cfg(feature = "rust-crypto")]
+        return CryptoBackend::RustCrypto;
+    }
+}
+
+impl CryptoBackend {
+    pub fn public_key_from_pem(
+        self,
+        pem: &[u8],
+    ) -> Result<Box<dyn SignatureRecovery>, DecryptionError> {
+        match self {
+            #[cfg(feature = "openssl")]
+            CryptoBackend::OpenSsl => Ok(Box::new(openssl::public_key_from_pem(pem)?)),
+            #[cfg(feature = "rust-crypto")]
+            CryptoBackend::RustCrypto => Ok(Box::new(rust_crypto::public_key_from_pem(pem)?)),
+        }
+    }
+
+    pub fn private_key_from_pem(self, pem: &[u8]) -> Result<Box<dyn MessageSigner>, SigningError> {
+        match self {
+            #[cfg(feature = "openssl")]
+            CryptoBackend::OpenSsl => Ok(Box::new(openssl::private_key_from_pem(pem)?)),
+            #[cfg(feature = "rust-crypto")]
+            CryptoBackend::RustCrypto => Ok(Box::new(rust_crypto::private_key_from_pem(pem)?)),
+        }
+    }
+}
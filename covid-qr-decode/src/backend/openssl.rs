@@ -0,0 +1,38 @@
+use super::{MessageSigner, SignatureRecovery};
+use crate::error::{DecryptionError, SigningError};
+use openssl::{
+    pkey::{Private, Public},
+    rsa::{Padding, Rsa},
+};
+
+const DEFAULT_PADDING: Padding = Padding::PKCS1;
+
+pub(crate) fn public_key_from_pem(pem: &[u8]) -> Result<Rsa<Public>, DecryptionError> {
+    Ok(Rsa::public_key_from_pem(pem)?)
+}
+
+pub(crate) fn private_key_from_pem(pem: &[u8]) -> Result<Rsa<Private>, SigningError> {
+    Ok(Rsa::private_key_from_pem(pem)?)
+}
+
+impl SignatureRecovery for Rsa<Public> {
+    fn recover(&self, signature: &[u8]) -> Result<Vec<u8>, DecryptionError> {
+        let mut buf: Vec<u8> = vec![0; self.size() as usize];
+        let out_len = self.public_decrypt(signature, &mut buf, DEFAULT_PADDING)?;
+
+        buf.truncate(out_len);
+
+        Ok(buf)
+    }
+}
+
+impl MessageSigner for Rsa<Private> {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, SigningError> {
+        let mut buf: Vec<u8> = vec![0; self.size() as usize];
+        let out_len = self.private_encrypt(message, &mut buf, DEFAULT_PADDING)?;
+
+        buf.truncate(out_len);
+
+        Ok(buf)
+    }
+}
@@ -0,0 +1,71 @@
+use super::{MessageSigner, SignatureRecovery};
+use crate::error::{DecryptionError, SigningError};
+use rsa::pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey};
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use rsa::traits::PublicKeyParts;
+use rsa::{BigUint, Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+
+pub(crate) fn public_key_from_pem(pem: &[u8]) -> Result<RsaPublicKey, DecryptionError> {
+    let pem = std::str::from_utf8(pem).map_err(|e| DecryptionError::MalformedKey(e.to_string()))?;
+
+    // SubjectPublicKeyInfo ("BEGIN PUBLIC KEY") or PKCS#1 ("BEGIN RSA PUBLIC KEY")
+    RsaPublicKey::from_public_key_pem(pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+        .map_err(|e| DecryptionError::MalformedKey(e.to_string()))
+}
+
+pub(crate) fn private_key_from_pem(pem: &[u8]) -> Result<RsaPrivateKey, SigningError> {
+    let pem = std::str::from_utf8(pem).map_err(|e| SigningError::MalformedKey(e.to_string()))?;
+
+    // PKCS#8 ("BEGIN PRIVATE KEY") or PKCS#1 ("BEGIN RSA PRIVATE KEY")
+    RsaPrivateKey::from_pkcs8_pem(pem)
+        .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+        .map_err(|e| SigningError::MalformedKey(e.to_string()))
+}
+
+impl SignatureRecovery for RsaPublicKey {
+    fn recover(&self, signature: &[u8]) -> Result<Vec<u8>, DecryptionError> {
+        let size = self.size();
+
+        if signature.len() != size {
+            return Err(DecryptionError::MalformedSignature);
+        }
+
+        let s = BigUint::from_bytes_be(signature);
+        if &s >= self.n() {
+            return Err(DecryptionError::MalformedSignature);
+        }
+
+        let m = s.modpow(self.e(), self.n()).to_bytes_be();
+
+        // left-pad to the key size; leading zeros are lost in the conversion
+        let mut em = vec![0; size - m.len()];
+        em.extend_from_slice(&m);
+
+        // EM = 0x00 || 0x01 || PS (at least 8 bytes of 0xff) || 0x00 || M
+        if em.len() < 11 || em[0] != 0x00 || em[1] != 0x01 {
+            return Err(DecryptionError::MalformedSignature);
+        }
+
+        let padding_len = em[2..]
+            .iter()
+            .position(|&b| b != 0xff)
+            .ok_or(DecryptionError::MalformedSignature)?;
+
+        if padding_len < 8 || em[2 + padding_len] != 0x00 {
+            return Err(DecryptionError::MalformedSignature);
+        }
+
+        Ok(em.split_off(2 + padding_len + 1))
+    }
+}
+
+impl MessageSigner for RsaPrivateKey {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, SigningError> {
+        Ok(self.sign_with_rng(
+            &mut rand::thread_rng(),
+            Pkcs1v15Sign::new_unprefixed(),
+            message,
+        )?)
+    }
+}
@@ -0,0 +1,92 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Time zone the certificates are issued in, and their dates are meant in
+pub const CERTIFICATE_TIME_ZONE: Tz = chrono_tz::Europe::Warsaw;
+
+/// Source of the current time for validity checks
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Current date in [`CERTIFICATE_TIME_ZONE`]
+    fn today(&self) -> NaiveDate {
+        certificate_date(&self.now())
+    }
+}
+
+/// The system clock
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock stopped at a given instant, e.g. to check validity as of another date
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FixedClock(DateTime<Utc>);
+
+impl FixedClock {
+    pub fn at<T: TimeZone>(instant: &DateTime<T>) -> Self {
+        FixedClock(instant.with_timezone(&Utc))
+    }
+
+    /// Noon of `date` in [`CERTIFICATE_TIME_ZONE`]
+    pub fn on(date: NaiveDate) -> Self {
+        // noon is never skipped or repeated by daylight saving time changes
+        let noon = CERTIFICATE_TIME_ZONE
+            .from_local_datetime(&date.and_hms(12, 0, 0))
+            .single()
+            .expect("noon is unambiguous");
+
+        Self::at(&noon)
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// Date of `instant` in [`CERTIFICATE_TIME_ZONE`]
+pub fn certificate_date<T: TimeZone>(instant: &DateTime<T>) -> NaiveDate {
+    instant
+        .with_timezone(&CERTIFICATE_TIME_ZONE)
+        .date()
+        .naive_local()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    #[test]
+    fn warsaw_date() {
+        // 23:30 UTC is already the next day in Warsaw, in winter and in summer
+        let winter = Utc.ymd(2021, 12, 31).and_hms(23, 30, 0);
+        check!(certificate_date(&winter) == NaiveDate::from_ymd(2022, 1, 1));
+
+        let summer = Utc.ymd(2021, 6, 30).and_hms(22, 30, 0);
+        check!(certificate_date(&summer) == NaiveDate::from_ymd(2021, 7, 1));
+
+        let before = Utc.ymd(2021, 12, 31).and_hms(22, 59, 59);
+        check!(certificate_date(&before) == NaiveDate::from_ymd(2021, 12, 31));
+    }
+
+    #[test]
+    fn fixed_clock() {
+        let date = NaiveDate::from_ymd(2021, 3, 28);
+        // the day daylight saving time starts
+        check!(FixedClock::on(date).today() == date);
+        check!(FixedClock::on(date).now() == Utc.ymd(2021, 3, 28).and_hms(10, 0, 0));
+
+        let instant = chrono::FixedOffset::west(5 * 3600)
+            .ymd(2021, 12, 31)
+            .and_hms(20, 0, 0);
+        check!(FixedClock::at(&instant).today() == NaiveDate::from_ymd(2022, 1, 1));
+    }
+}
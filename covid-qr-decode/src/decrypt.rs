@@ -1,32 +1,42 @@
+use crate::backend::{CryptoBackend, SignatureRecovery};
 use crate::error::DecryptionError;
-use openssl::{
-    pkey::Public,
-    rsa::{Padding, Rsa},
-};
+#[cfg(feature = "openssl")]
+use openssl::{pkey::Public, rsa::Rsa};
 
 pub struct RsaDecrypter {
-    key: Rsa<Public>,
+    key: Box<dyn SignatureRecovery>,
 }
 
 impl RsaDecrypter {
-    const DEFAULT_PADDING: Padding = Padding::PKCS1;
-
+    #[cfg(feature = "openssl")]
     pub fn new(key: Rsa<Public>) -> Self {
+        RsaDecrypter { key: Box::new(key) }
+    }
+
+    pub fn with_backend(key: Box<dyn SignatureRecovery>) -> Self {
         RsaDecrypter { key }
     }
 
+    pub fn from_pem(pem: impl AsRef<[u8]>) -> Result<Self, DecryptionError> {
+        Self::from_pem_with(pem, CryptoBackend::default())
+    }
+
+    pub fn from_pem_with(
+        pem: impl AsRef<[u8]>,
+        backend: CryptoBackend,
+    ) -> Result<Self, DecryptionError> {
+        let key = backend.public_key_from_pem(pem.as_ref())?;
+
+        Ok(RsaDecrypter { key })
+    }
+
     pub fn decrypt(&self, ciphertext: impl AsRef<[u8]>) -> Result<String, DecryptionError> {
-        let mut buf: Vec<u8> = vec![0; self.key.size() as usize];
         let ciphertext = ciphertext.as_ref();
-        let out_len = self
-            .key
-            .public_decrypt(ciphertext, &mut buf, Self::DEFAULT_PADDING)?;
+        let buf = self.key.recover(ciphertext)?;
 
-        if out_len == 0 {
+        if buf.is_empty() {
             Err(DecryptionError::NoData)
         } else {
-            buf.truncate(out_len);
-
             // try to decode as utf-8
             let s = std::str::from_utf8(&buf)?;
 
@@ -38,15 +48,15 @@ impl RsaDecrypter {
 impl Default for RsaDecrypter {
     fn default() -> Self {
         let pem = include_bytes!("../keys/publiczny_klucz_podpisu.pub");
-        let key = Rsa::public_key_from_pem(&pem[..]).expect("Malformed default RSA key");
 
-        RsaDecrypter { key }
+        RsaDecrypter::from_pem(&pem[..]).expect("Malformed default RSA key")
     }
 }
 
+#[cfg(feature = "openssl")]
 impl From<Rsa<Public>> for RsaDecrypter {
     fn from(key: Rsa<Public>) -> Self {
-        RsaDecrypter { key }
+        RsaDecrypter::new(key)
     }
 }
 
@@ -58,25 +68,24 @@ mod tests {
     mod data {
         use super::*;
 
-        pub fn decrypter() -> RsaDecrypter {
+        pub fn decrypter(backend: CryptoBackend) -> RsaDecrypter {
             let pem = include_bytes!("../keys/test_public.pem");
-            let key = Rsa::public_key_from_pem(&pem[..]).expect("Malformed test RSA key");
 
-            RsaDecrypter::from(key)
+            RsaDecrypter::from_pem_with(&pem[..], backend).expect("Malformed test RSA key")
         }
 
         pub fn case1() -> (&'static str, &'static [u8]) {
             let plain = include_str!("../testdata/1.plain");
             let cipher = include_bytes!("../testdata/1.cipher");
 
-            (&plain[..], &cipher[..])
+            (plain, &cipher[..])
         }
 
         pub fn case2() -> (&'static str, &'static [u8]) {
             let plain = include_str!("../testdata/2.plain");
             let cipher = include_bytes!("../testdata/2.cipher");
 
-            (&plain[..], &cipher[..])
+            (plain, &cipher[..])
         }
 
         pub fn malformed() -> &'static [u8] {
@@ -98,50 +107,69 @@ mod tests {
         }
     }
 
-    #[test]
-    fn correct1() {
-        let (plain, cipher) = data::case1();
+    // every backend has to pass the same suite; only the malformed signature error differs
+    macro_rules! backend_tests {
+        ($name:ident, $backend:expr, malformed: $($malformed:tt)+) => {
+            mod $name {
+                use super::*;
 
-        let dec = data::decrypter();
+                #[test]
+                fn correct1() {
+                    let (plain, cipher) = data::case1();
 
-        let_assert!(Ok(dec_plain) = dec.decrypt(&cipher));
-        check!(dec_plain == plain);
-    }
+                    let dec = data::decrypter($backend);
 
-    #[test]
-    fn correct2() {
-        let (plain, cipher) = data::case2();
+                    let_assert!(Ok(dec_plain) = dec.decrypt(cipher));
+                    check!(dec_plain == plain);
+                }
 
-        let dec = data::decrypter();
+                #[test]
+                fn correct2() {
+                    let (plain, cipher) = data::case2();
 
-        let_assert!(Ok(dec_plain) = dec.decrypt(&cipher));
-        check!(dec_plain == plain);
-    }
+                    let dec = data::decrypter($backend);
 
-    #[test]
-    fn empty() {
-        let cipher = data::empty();
+                    let_assert!(Ok(dec_plain) = dec.decrypt(cipher));
+                    check!(dec_plain == plain);
+                }
 
-        let dec = data::decrypter();
+                #[test]
+                fn empty() {
+                    let cipher = data::empty();
 
-        let_assert!(Err(DecryptionError::NoData) = dec.decrypt(&cipher));
-    }
+                    let dec = data::decrypter($backend);
 
-    #[test]
-    fn malformed() {
-        let cipher = data::malformed();
+                    let_assert!(Err(DecryptionError::NoData) = dec.decrypt(cipher));
+                }
 
-        let dec = data::decrypter();
+                #[test]
+                fn malformed() {
+                    let cipher = data::malformed();
 
-        let_assert!(Err(DecryptionError::Ssl(_)) = dec.decrypt(&cipher));
-    }
+                    let dec = data::decrypter($backend);
+
+                    let_assert!(Err($($malformed)+) = dec.decrypt(cipher));
+                }
 
-    #[test]
-    fn invalid_utf8() {
-        let cipher = data::invalid_utf8();
+                #[test]
+                fn invalid_utf8() {
+                    let cipher = data::invalid_utf8();
 
-        let dec = data::decrypter();
+                    let dec = data::decrypter($backend);
 
-        let_assert!(Err(DecryptionError::InvalidUtf8(_)) = dec.decrypt(&cipher));
+                    let_assert!(Err(DecryptionError::InvalidUtf8(_)) = dec.decrypt(cipher));
+                }
+            }
+        };
     }
+
+    #[cfg(feature = "openssl")]
+    backend_tests!(openssl, CryptoBackend::OpenSsl, malformed: DecryptionError::Ssl(_));
+
+    #[cfg(feature = "rust-crypto")]
+    backend_tests!(
+        rust_crypto,
+        CryptoBackend::RustCrypto,
+        malformed: DecryptionError::MalformedSignature
+    );
 }
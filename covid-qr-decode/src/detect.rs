@@ -0,0 +1,459 @@
+use crate::error::{DetectError, PdfError, QrError};
+use crate::pdf::{PdfImage, PdfQrExtractor};
+use crate::preprocess::{Attempt, Preprocess};
+use crate::qr::{FoundCode, Point, QrDecoder, PAYLOAD_VERSION};
+use crate::vaccination_info::VaccinationInfo;
+use image::ImageFormat;
+use std::str::FromStr;
+
+// RSA-2048 signatures, as issued by the ministry
+const CIPHERTEXT_LEN: usize = 256;
+// the header may follow some junk, which readers are expected to skip
+const PDF_HEADER_WINDOW: usize = 1024;
+
+/// Kind of input holding a certificate
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum InputKind {
+    Pdf,
+    /// Image with a QR code, e.g. a PNG or a photo
+    Image,
+    /// QR code payload, `1;` followed by the base64-encoded ciphertext
+    Base64,
+    /// Raw ciphertext
+    Encrypted,
+    /// Plaintext record, which carries no signature
+    Record,
+}
+
+/// How sure [`detect`] is about the input kind
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Confidence {
+    /// Only the size fits, e.g. binary data as long as a ciphertext
+    Low,
+    /// The structure fits, but the content didn't check out
+    Medium,
+    /// A file signature matched, or the content parsed
+    High,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Detection {
+    pub kind: InputKind,
+    pub confidence: Confidence,
+}
+
+/// Where a ciphertext was read from, for PDFs and images
+///
+/// For PDF pages and images that failed to load, only the page and image are filled in.
+#[derive(Debug, Clone, Default)]
+pub struct CodeLocation {
+    /// Page number, for PDFs
+    pub page: Option<u32>,
+    /// Name of the image on the page, for PDFs
+    pub image: Option<String>,
+    pub corners: [Point; 4],
+    /// QR code backend that read the code
+    pub backend: String,
+    /// Preprocessing needed to read the code, empty if none was
+    pub preprocessing: Vec<Preprocess>,
+}
+
+impl From<&FoundCode> for CodeLocation {
+    fn from(code: &FoundCode) -> Self {
+        CodeLocation {
+            page: None,
+            image: None,
+            corners: code.corners,
+            backend: code.backend.clone(),
+            preprocessing: code.preprocessing.clone(),
+        }
+    }
+}
+
+/// Ciphertext to verify, one per QR code found
+#[derive(Debug)]
+pub struct Candidate {
+    pub location: Option<CodeLocation>,
+    pub ciphertext: Result<Vec<u8>, DetectError>,
+}
+
+impl Candidate {
+    fn bare(ciphertext: Vec<u8>) -> Self {
+        Candidate {
+            location: None,
+            ciphertext: Ok(ciphertext),
+        }
+    }
+}
+
+impl From<FoundCode> for Candidate {
+    fn from(code: FoundCode) -> Self {
+        Candidate {
+            location: Some(CodeLocation::from(&code)),
+            ciphertext: code.into_encrypted().map_err(DetectError::from),
+        }
+    }
+}
+
+/// What the input holds
+#[derive(Debug)]
+pub enum Content {
+    /// Ciphertexts in the order they were found; PDFs and images may hold several codes
+    Ciphertext(Vec<Candidate>),
+    Record(String),
+}
+
+#[derive(Debug)]
+pub struct Detected {
+    pub detection: Detection,
+    pub content: Content,
+}
+
+/// Tells the input kind from the content alone
+pub fn detect(data: &[u8]) -> Result<Detection, DetectError> {
+    let detection = |kind, confidence| Ok(Detection { kind, confidence });
+
+    if data.iter().all(u8::is_ascii_whitespace) {
+        return Err(DetectError::Empty);
+    }
+
+    if data.starts_with(b"%PDF-") {
+        return detection(InputKind::Pdf, Confidence::High);
+    }
+    let header = &data[..data.len().min(PDF_HEADER_WINDOW)];
+    if header.windows(5).any(|w| w == b"%PDF-") {
+        return detection(InputKind::Pdf, Confidence::Medium);
+    }
+
+    match image::guess_format(data) {
+        Ok(ImageFormat::Png)
+        | Ok(ImageFormat::Jpeg)
+        | Ok(ImageFormat::Gif)
+        | Ok(ImageFormat::WebP)
+        | Ok(ImageFormat::Tiff)
+        | Ok(ImageFormat::Bmp) => return detection(InputKind::Image, Confidence::High),
+        // short signatures, more likely to match by chance
+        Ok(_) => return detection(InputKind::Image, Confidence::Medium),
+        Err(_) => {}
+    }
+
+    if let Ok(text) = std::str::from_utf8(data) {
+        let text = text.trim();
+
+        // the record fields and the payload version are separated with ';',
+        // which isn't in the base64 alphabet
+        match text.split_once(';') {
+            Some((_, rest)) if rest.contains(';') => {
+                let confidence = match VaccinationInfo::from_str(text) {
+                    Ok(_) => Confidence::High,
+                    Err(_) => Confidence::Medium,
+                };
+
+                return detection(InputKind::Record, confidence);
+            }
+            Some((version, payload)) if version.bytes().all(|b| b.is_ascii_digit()) => {
+                if let Ok(ciphertext) = base64::decode(payload) {
+                    let confidence =
+                        if version == PAYLOAD_VERSION && ciphertext.len() == CIPHERTEXT_LEN {
+                            Confidence::High
+                        } else {
+                            Confidence::Medium
+                        };
+
+                    return detection(InputKind::Base64, confidence);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // ciphertext looks like random bytes, so only the size tells
+    if data.len() == CIPHERTEXT_LEN {
+        return detection(InputKind::Encrypted, Confidence::Low);
+    }
+
+    Err(DetectError::Unrecognized)
+}
+
+/// Detects the input kind and reads the certificate from it, with the default QR decoder
+pub fn detect_and_decode(data: &[u8]) -> Result<Detected, DetectError> {
+    detect_and_decode_with(data, &mut QrDecoder::new())
+}
+
+/// Like [`detect_and_decode`], reading QR codes with the given decoder
+pub fn detect_and_decode_with(data: &[u8], qr: &mut QrDecoder) -> Result<Detected, DetectError> {
+    let detection = detect(data)?;
+
+    Ok(Detected {
+        detection,
+        content: decode_as(detection.kind, data, qr)?,
+    })
+}
+
+/// Reads the certificate from an input of a known kind
+///
+/// Fails if a PDF or image holds no QR codes at all. PDF pages and images that can't
+/// be read are reported as failed candidates, after the codes.
+pub fn decode_as(kind: InputKind, data: &[u8], qr: &mut QrDecoder) -> Result<Content, DetectError> {
+    decode_recording(kind, data, qr, &mut Vec::new())
+}
+
+/// Like [`decode_as`], adding every QR code detection pass to `attempts`
+pub(crate) fn decode_recording(
+    kind: InputKind,
+    data: &[u8],
+    qr: &mut QrDecoder,
+    attempts: &mut Vec<Attempt>,
+) -> Result<Content, DetectError> {
+    let candidates = match kind {
+        InputKind::Pdf => pdf_candidates(data, qr, attempts)?,
+        InputKind::Image => {
+            let image = image::load_from_memory(data).map_err(QrError::from)?;
+            let scan = qr.image_scan(image)?;
+            attempts.extend(scan.attempts);
+
+            let codes = scan.codes;
+            if codes.is_empty() {
+                return Err(QrError::NoData.into());
+            }
+
+            codes.into_iter().map(Candidate::from).collect()
+        }
+        InputKind::Base64 => {
+            let payload = std::str::from_utf8(data)?;
+
+            vec![Candidate::bare(QrDecoder::decode_payload(
+                payload.trim_end(),
+            )?)]
+        }
+        InputKind::Encrypted => vec![Candidate::bare(data.to_vec())],
+        InputKind::Record => return Ok(Content::Record(std::str::from_utf8(data)?.to_owned())),
+    };
+
+    Ok(Content::Ciphertext(candidates))
+}
+
+fn pdf_candidates(
+    data: &[u8],
+    qr: &mut QrDecoder,
+    attempts: &mut Vec<Attempt>,
+) -> Result<Vec<Candidate>, DetectError> {
+    let pdf = PdfQrExtractor::from_bytes(data)?;
+
+    let mut candidates = Vec::new();
+    // reported after the codes, which are more relevant
+    let mut errors = Vec::new();
+
+    for page in 1..=pdf.page_count() {
+        let found = candidates.len();
+        for image in pdf.images_on_page(page) {
+            scan_pdf_image(image, qr, attempts, &mut candidates, &mut errors);
+        }
+
+        // rasterizing is slow, and only needed for codes drawn as vector shapes
+        if candidates.len() == found {
+            if let Some(drawing) = pdf.page_drawing(page) {
+                scan_pdf_image(drawing, qr, attempts, &mut candidates, &mut errors);
+            }
+        }
+    }
+
+    if candidates.is_empty() && errors.is_empty() {
+        return Err(PdfError::QrNotFound.into());
+    }
+
+    candidates.extend(errors);
+
+    Ok(candidates)
+}
+
+fn scan_pdf_image(
+    image: Result<PdfImage, PdfError>,
+    qr: &mut QrDecoder,
+    attempts: &mut Vec<Attempt>,
+    candidates: &mut Vec<Candidate>,
+    errors: &mut Vec<Candidate>,
+) {
+    let PdfImage { page, name, image } = match image {
+        Ok(image) => image,
+        Err(e) => {
+            errors.push(Candidate {
+                location: failed_location(&e),
+                ciphertext: Err(e.into()),
+            });
+            return;
+        }
+    };
+
+    match qr.image_scan(image) {
+        Ok(scan) => {
+            attempts.extend(scan.attempts);
+            candidates.extend(scan.codes.into_iter().map(|code| {
+                let mut candidate = Candidate::from(code);
+                if let Some(location) = &mut candidate.location {
+                    location.page = Some(page);
+                    location.image = Some(name.clone());
+                }
+                candidate
+            }));
+        }
+        Err(e) => errors.push(Candidate {
+            location: Some(CodeLocation {
+                page: Some(page),
+                image: Some(name),
+                ..CodeLocation::default()
+            }),
+            ciphertext: Err(e.into()),
+        }),
+    }
+}
+
+/// Page and image a PDF error refers to
+fn failed_location(e: &PdfError) -> Option<CodeLocation> {
+    let (page, image) = match e {
+        PdfError::Page { page, .. } => (*page, None),
+        PdfError::Image { page, name, .. } => (*page, Some(name.clone())),
+        _ => return None,
+    };
+
+    Some(CodeLocation {
+        page: Some(page),
+        image,
+        ..CodeLocation::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PdfQrGenerator;
+    use assert2::{check, let_assert};
+
+    mod data {
+        pub fn read(name: &str) -> Vec<u8> {
+            std::fs::read(format!("testdata/{}", name)).expect("Unable to read test data")
+        }
+    }
+
+    fn check_detect(data: &[u8], kind: InputKind, confidence: Confidence) {
+        let_assert!(Ok(detection) = detect(data));
+        check!(detection == Detection { kind, confidence });
+    }
+
+    #[test]
+    fn signatures() {
+        check_detect(&data::read("1.pdf"), InputKind::Pdf, Confidence::High);
+        check_detect(&data::read("1.png"), InputKind::Image, Confidence::High);
+        check_detect(
+            &data::read("1.payload"),
+            InputKind::Base64,
+            Confidence::High,
+        );
+        check_detect(&data::read("1.plain"), InputKind::Record, Confidence::High);
+        check_detect(
+            &data::read("1.cipher"),
+            InputKind::Encrypted,
+            Confidence::Low,
+        );
+    }
+
+    #[test]
+    fn partial_matches() {
+        let mut pdf = b"junk\n".to_vec();
+        pdf.extend(data::read("1.pdf"));
+        check_detect(&pdf, InputKind::Pdf, Confidence::Medium);
+
+        check_detect(b"2;AAAA\n", InputKind::Base64, Confidence::Medium);
+        check_detect(
+            b"123456;1;not a record",
+            InputKind::Record,
+            Confidence::Medium,
+        );
+    }
+
+    #[test]
+    fn unrecognized() {
+        let_assert!(Err(DetectError::Empty) = detect(b""));
+        let_assert!(Err(DetectError::Empty) = detect(b" \n"));
+        let_assert!(Err(DetectError::Unrecognized) = detect(b"hello"));
+        let_assert!(Err(DetectError::Unrecognized) = detect(&[0xff; 100]));
+    }
+
+    #[test]
+    fn decode_all_kinds() {
+        let cipher = data::read("1.cipher");
+
+        for name in &["1.pdf", "1.png", "1.payload", "1.cipher"] {
+            let_assert!(Ok(detected) = detect_and_decode(&data::read(name)));
+            let_assert!(Content::Ciphertext(candidates) = detected.content);
+            let_assert!(
+                [Candidate {
+                    ciphertext: Ok(ciphertext),
+                    ..
+                }] = &candidates[..]
+            );
+            check!(ciphertext == &cipher, "{}", name);
+        }
+
+        let_assert!(Ok(detected) = detect_and_decode(&data::read("1.plain")));
+        let_assert!(Content::Record(record) = detected.content);
+        check!(record.as_bytes() == &data::read("1.plain")[..]);
+    }
+
+    #[test]
+    fn pdf_location() {
+        let_assert!(
+            Ok(Content::Ciphertext(candidates)) =
+                decode_as(InputKind::Pdf, &data::read("1.pdf"), &mut QrDecoder::new())
+        );
+
+        let_assert!(
+            [Candidate {
+                location: Some(location),
+                ..
+            }] = &candidates[..]
+        );
+        check!(location.page == Some(1));
+        check!(location.image.is_some());
+    }
+
+    #[test]
+    fn failed_image_location() {
+        let image = image::load_from_memory(&data::read("1.png")).unwrap();
+
+        // claim a larger width than the image data has, keeping the xref offsets intact
+        let mut pdf = PdfQrGenerator::default().generate(&image);
+        let width = format!("/Width {}", image.to_luma8().width()).into_bytes();
+        let_assert!(Some(pos) = pdf.windows(width.len()).position(|w| w == &width[..]));
+        for digit in &mut pdf[pos + b"/Width ".len()..pos + width.len()] {
+            *digit = b'9';
+        }
+
+        let_assert!(
+            Ok(Content::Ciphertext(candidates)) =
+                decode_as(InputKind::Pdf, &pdf, &mut QrDecoder::new())
+        );
+        let_assert!(
+            [Candidate {
+                location: Some(location),
+                ciphertext: Err(DetectError::Pdf(PdfError::Image { .. })),
+            }] = &candidates[..]
+        );
+        check!(location.page == Some(1));
+        check!(location.image.as_deref() == Some("X0"));
+    }
+
+    #[test]
+    fn no_codes() {
+        let image = data::read("missing_qr.png");
+        let_assert!(Err(DetectError::Qr(QrError::NoData)) = detect_and_decode(&image));
+
+        let pdf = PdfQrGenerator::default().generate(&image::load_from_memory(&image).unwrap());
+        let_assert!(Err(DetectError::Pdf(PdfError::QrNotFound)) = detect_and_decode(&pdf));
+    }
+}
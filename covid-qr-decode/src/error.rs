@@ -1,14 +1,71 @@
+use crate::verify::VerificationError;
 use displaythis::Display;
 use thiserror::Error;
 
+/// Any failure of the crate, with a stable code to branch on
+///
+/// Each variant has a short message of its own and keeps the wrapped error as the
+/// [`source`](std::error::Error::source), so the whole chain can be logged without
+/// repeating itself. The same goes for the other errors wrapping errors of this crate.
 #[derive(Debug, Error)]
-pub enum DecodeError {
-    /// Malformed info string
-    #[error("malformed input line {0:?}")]
-    MalformedLine(#[from] MalformedLine),
+pub enum Error {
+    #[error("Unsupported input")]
+    Detect(#[source] DetectError),
+    #[error("Unable to read the PDF")]
+    Pdf(#[from] PdfError),
+    #[error("Unable to read the QR code")]
+    Qr(#[from] QrError),
+    #[error("Signature verification failed")]
+    Decryption(#[from] DecryptionError),
+    #[error("No trusted key verified the signature")]
+    Keyring(#[from] KeyringError),
+    #[error("Malformed record")]
+    Record(#[from] MalformedLine),
+    #[error("Signing failed")]
+    Signing(#[from] SigningError),
+    #[error("QR code rendering failed")]
+    Render(#[from] RenderError),
+}
+
+impl Error {
+    /// Stable, machine-readable error code, e.g. `QR_NOT_FOUND`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Detect(e) => e.code(),
+            Error::Pdf(e) => e.code(),
+            Error::Qr(e) => e.code(),
+            Error::Decryption(e) => e.code(),
+            Error::Keyring(e) => e.code(),
+            Error::Record(e) => e.code(),
+            Error::Signing(e) => e.code(),
+            Error::Render(e) => e.code(),
+        }
+    }
+}
+
+impl From<DetectError> for Error {
+    fn from(e: DetectError) -> Self {
+        // keep the PDF and QR code failures distinguishable
+        match e {
+            DetectError::Pdf(e) => Error::Pdf(e),
+            DetectError::Qr(e) => Error::Qr(e),
+            e => Error::Detect(e),
+        }
+    }
+}
+
+impl From<VerificationError> for Error {
+    fn from(e: VerificationError) -> Self {
+        match e {
+            VerificationError::Detection(e) | VerificationError::Extraction(e) => e.into(),
+            VerificationError::Signature(e) => Error::Keyring(e),
+            VerificationError::Record(e) => Error::Record(e),
+        }
+    }
 }
 
 #[derive(Display, Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FieldName {
     #[display("szczepienieId")]
     Id,
@@ -29,6 +86,11 @@ pub enum FieldName {
 }
 
 #[derive(Debug, Error, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "kind", content = "field", rename_all = "snake_case")
+)]
 pub enum MalformedLine {
     #[error("missing input field: {0:?}")]
     MissingField(FieldName),
@@ -36,16 +98,72 @@ pub enum MalformedLine {
     MalformedFieldData(FieldName),
 }
 
+impl MalformedLine {
+    pub fn code(&self) -> &'static str {
+        "RECORD_MALFORMED"
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum DecryptionError {
+    #[cfg(feature = "openssl")]
     #[error("SSL decryption error: {0}")]
     Ssl(#[from] openssl::error::ErrorStack),
+    #[error("Malformed public key: {0}")]
+    MalformedKey(String),
+    #[error("Malformed signature")]
+    MalformedSignature,
     #[error("Invalid UTF-8 string in the payload")]
     InvalidUtf8(#[from] std::str::Utf8Error),
     #[error("Empty payload; probably bad decryption key")]
     NoData,
 }
 
+impl DecryptionError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            DecryptionError::MalformedKey(_) => "KEY_MALFORMED",
+            DecryptionError::InvalidUtf8(_) => "PAYLOAD_MALFORMED",
+            _ => "SIGNATURE_INVALID",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum KeyringError {
+    #[error("No trusted key was valid on the issue date, {0}")]
+    NoApplicableKey(chrono::NaiveDate),
+    #[error("Invalid cryptographic signature; no trusted key matches")]
+    NoMatchingKey(Vec<(String, DecryptionError)>),
+}
+
+impl KeyringError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            KeyringError::NoApplicableKey(_) => "NO_APPLICABLE_KEY",
+            KeyringError::NoMatchingKey(_) => "SIGNATURE_INVALID",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SigningError {
+    #[cfg(feature = "openssl")]
+    #[error("SSL signing error: {0}")]
+    Ssl(#[from] openssl::error::ErrorStack),
+    #[cfg(feature = "rust-crypto")]
+    #[error("RSA signing error: {0}")]
+    Rsa(#[from] rsa::Error),
+    #[error("Malformed private key: {0}")]
+    MalformedKey(String),
+}
+
+impl SigningError {
+    pub fn code(&self) -> &'static str {
+        "SIGNING_FAILED"
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum QrError {
     #[error("QR code either not found or not supported")]
@@ -54,6 +172,10 @@ pub enum QrError {
     Extract(#[from] quircs::ExtractError),
     #[error("QR code decode failed: {0}")]
     Decode(#[from] quircs::DecodeError),
+    /// `rqrr` failure with no `quircs` counterpart
+    #[cfg(feature = "rqrr")]
+    #[error("QR code decode failed: {0}")]
+    Rqrr(rqrr::DeQRError),
     #[error("QR image read failed: {0}")]
     Image(#[from] image::ImageError),
     #[error("Invalid UTF-8 string in the payload: {0}")]
@@ -66,12 +188,172 @@ pub enum QrError {
     MalformedPayloadBase64(#[from] base64::DecodeError),
 }
 
+impl QrError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            QrError::NoData => "QR_NOT_FOUND",
+            QrError::Extract(_) | QrError::Decode(_) => "QR_UNREADABLE",
+            #[cfg(feature = "rqrr")]
+            QrError::Rqrr(_) => "QR_UNREADABLE",
+            QrError::Image(_) => "IMAGE_MALFORMED",
+            QrError::UnknownPayloadVersion(_) => "UNSUPPORTED_PAYLOAD_VERSION",
+            QrError::InvalidUtf8(_)
+            | QrError::MalformedPayload
+            | QrError::MalformedPayloadBase64(_) => "PAYLOAD_MALFORMED",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RenderError {
+    #[error("QR code encoding failed: {0}")]
+    Qr(#[from] qrcode::types::QrError),
+    #[error("QR image write failed: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("Unknown error correction level {0:?}")]
+    UnknownErrorCorrection(String),
+}
+
+impl RenderError {
+    pub fn code(&self) -> &'static str {
+        "RENDER_FAILED"
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum PdfError {
     #[error("PDF processing error")]
     PdfProcessing(#[from] pdf::error::PdfError),
+    #[error("PDF read failed: {0}")]
+    Io(#[from] std::io::Error),
     #[error("QR code not found")]
     QrNotFound,
     #[error("Image conversion error")]
     ImageConversion,
+    #[error("Unsupported image filter {0}")]
+    UnsupportedFilter(String),
+    #[error("Unsupported image color space {0}")]
+    UnsupportedColorSpace(String),
+    #[error("Unable to load page {page}")]
+    Page {
+        page: u32,
+        #[source]
+        source: Box<PdfError>,
+    },
+    #[error("Unable to load image {name} on page {page}")]
+    Image {
+        page: u32,
+        name: String,
+        #[source]
+        source: Box<PdfError>,
+    },
+}
+
+impl PdfError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PdfError::QrNotFound => "QR_NOT_FOUND",
+            PdfError::PdfProcessing(_) => "PDF_MALFORMED",
+            PdfError::Io(_) => "IO_ERROR",
+            PdfError::ImageConversion => "IMAGE_MALFORMED",
+            PdfError::UnsupportedFilter(_) | PdfError::UnsupportedColorSpace(_) => {
+                "IMAGE_UNSUPPORTED"
+            }
+            PdfError::Page { source, .. } | PdfError::Image { source, .. } => source.code(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DetectError {
+    #[error("Empty input")]
+    Empty,
+    #[error("Unrecognized input format")]
+    Unrecognized,
+    #[error("Input is not valid UTF-8 text: {0}")]
+    InvalidUtf8(#[from] std::str::Utf8Error),
+    #[error("Unable to read the PDF")]
+    Pdf(#[from] PdfError),
+    #[error("Unable to read the QR code")]
+    Qr(#[from] QrError),
+}
+
+impl DetectError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            DetectError::Empty | DetectError::Unrecognized => "UNSUPPORTED_FILE_TYPE",
+            DetectError::InvalidUtf8(_) => "PAYLOAD_MALFORMED",
+            DetectError::Pdf(e) => e.code(),
+            DetectError::Qr(e) => e.code(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{check, let_assert};
+    use std::error::Error as _;
+
+    #[test]
+    fn codes() {
+        let e = Error::from(DetectError::Pdf(PdfError::QrNotFound));
+        let_assert!(Error::Pdf(PdfError::QrNotFound) = &e);
+        check!(e.code() == "QR_NOT_FOUND");
+
+        check!(Error::from(DetectError::Unrecognized).code() == "UNSUPPORTED_FILE_TYPE");
+        check!(
+            Error::from(QrError::UnknownPayloadVersion(2)).code() == "UNSUPPORTED_PAYLOAD_VERSION"
+        );
+        check!(Error::from(KeyringError::NoMatchingKey(vec![])).code() == "SIGNATURE_INVALID");
+        check!(
+            Error::from(MalformedLine::MissingField(FieldName::Id)).code() == "RECORD_MALFORMED"
+        );
+    }
+
+    fn chain(e: &(dyn std::error::Error + 'static)) -> Vec<String> {
+        std::iter::successors(Some(e), |&e| e.source())
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn source_chain() {
+        let e = Error::from(PdfError::Page {
+            page: 2,
+            source: Box::new(PdfError::UnsupportedFilter("JPXDecode".into())),
+        });
+        check!(e.code() == "IMAGE_UNSUPPORTED");
+        check!(
+            chain(&e)
+                == [
+                    "Unable to read the PDF",
+                    "Unable to load page 2",
+                    "Unsupported image filter JPXDecode",
+                ]
+        );
+
+        // no level is skipped, even for errors without a source of their own
+        let e = Error::from(QrError::NoData);
+        let_assert!(Some(source) = e.source());
+        let_assert!(Some(QrError::NoData) = source.downcast_ref());
+        check!(source.source().is_none());
+
+        let e = Error::from(MalformedLine::MissingField(FieldName::Id));
+        check!(chain(&e) == ["Malformed record", "missing input field: Id"]);
+        let_assert!(Some(source) = e.source());
+        let_assert!(Some(MalformedLine::MissingField(FieldName::Id)) = source.downcast_ref());
+
+        let e = Error::from(KeyringError::NoMatchingKey(vec![]));
+        check!(
+            chain(&e)
+                == [
+                    "No trusted key verified the signature",
+                    "Invalid cryptographic signature; no trusted key matches",
+                ]
+        );
+
+        let e = Error::from(DecryptionError::MalformedSignature);
+        check!(chain(&e) == ["Signature verification failed", "Malformed signature"]);
+    }
 }
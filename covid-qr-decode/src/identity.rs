@@ -0,0 +1,207 @@
+use crate::vaccination_info::VaccinationInfo;
+use chrono::{Datelike, NaiveDate};
+use std::collections::HashSet;
+
+/// How a record field compares with the identity document
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum FieldMatch {
+    Match,
+    /// The record lists only some of the names on the document, e.g. one of the given names
+    Partial,
+    Mismatch,
+}
+
+/// Field by field comparison of a record with an identity document
+///
+/// The record only holds the first letter of the surname and the day and month of birth,
+/// so holders sharing those can't be told apart.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IdentityMatch {
+    pub given_names: FieldMatch,
+    pub surname_initial: FieldMatch,
+    /// Day and month only
+    pub birthdate: FieldMatch,
+}
+
+impl IdentityMatch {
+    /// No field contradicts the document; given names may match only partially
+    pub fn is_consistent(&self) -> bool {
+        [self.given_names, self.surname_initial, self.birthdate]
+            .iter()
+            .all(|&field| field != FieldMatch::Mismatch)
+    }
+}
+
+/// Holder's details, as printed on an ID card or passport
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityDocument {
+    given_names: String,
+    surname: String,
+    birthdate: NaiveDate,
+}
+
+impl IdentityDocument {
+    pub fn new(
+        given_names: impl Into<String>,
+        surname: impl Into<String>,
+        birthdate: NaiveDate,
+    ) -> Self {
+        IdentityDocument {
+            given_names: given_names.into(),
+            surname: surname.into(),
+            birthdate,
+        }
+    }
+
+    /// Splits `Anna Maria Kowalska` into the given names and the last word as the surname
+    pub fn from_full_name(full_name: &str, birthdate: NaiveDate) -> Self {
+        let full_name = full_name.trim();
+        let (given_names, surname) = full_name
+            .rsplit_once(char::is_whitespace)
+            .unwrap_or(("", full_name));
+
+        Self::new(given_names.trim_end(), surname, birthdate)
+    }
+
+    /// Compares case-insensitively, with or without Polish diacritics
+    pub fn compare(&self, info: &VaccinationInfo) -> IdentityMatch {
+        IdentityMatch {
+            given_names: compare_names(info.names(), &self.given_names),
+            surname_initial: match self.surname.chars().next() {
+                Some(initial) if fold(initial) == fold(info.first_surname_letter()) => {
+                    FieldMatch::Match
+                }
+                _ => FieldMatch::Mismatch,
+            },
+            birthdate: if info.short_birthdate().month() == self.birthdate.month()
+                && info.short_birthdate().day() == self.birthdate.day()
+            {
+                FieldMatch::Match
+            } else {
+                FieldMatch::Mismatch
+            },
+        }
+    }
+}
+
+fn compare_names(record: &str, document: &str) -> FieldMatch {
+    let names = |s: &str| -> HashSet<String> {
+        s.split(|c: char| c.is_whitespace() || c == '-')
+            .filter(|name| !name.is_empty())
+            .map(|name| name.chars().map(fold).collect())
+            .collect()
+    };
+
+    let record = names(record);
+    let document = names(document);
+
+    // the record may leave out some of the names, but not have ones the document lacks
+    if record.is_empty() || !record.is_subset(&document) {
+        FieldMatch::Mismatch
+    } else if record == document {
+        FieldMatch::Match
+    } else {
+        FieldMatch::Partial
+    }
+}
+
+/// Lowercase letter without Polish diacritics
+fn fold(c: char) -> char {
+    match c.to_lowercase().next().unwrap_or(c) {
+        'ą' => 'a',
+        'ć' => 'c',
+        'ę' => 'e',
+        'ł' => 'l',
+        'ń' => 'n',
+        'ó' => 'o',
+        'ś' => 's',
+        'ź' | 'ż' => 'z',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+    use std::str::FromStr;
+
+    mod data {
+        use super::*;
+
+        pub fn info(names: &str, letter: char) -> VaccinationInfo {
+            VaccinationInfo::from_str(&format!(
+                "123456;1;20-01-2021;{};{};17-04;20-01-2022;321",
+                names, letter
+            ))
+            .expect("Malformed test record")
+        }
+
+        pub fn birthdate() -> NaiveDate {
+            NaiveDate::from_ymd(1990, 4, 17)
+        }
+    }
+
+    #[test]
+    fn full_match() {
+        let document = IdentityDocument::new("Anna", "Kowalska", data::birthdate());
+
+        check!(
+            document.compare(&data::info("Anna", 'K'))
+                == IdentityMatch {
+                    given_names: FieldMatch::Match,
+                    surname_initial: FieldMatch::Match,
+                    birthdate: FieldMatch::Match,
+                }
+        );
+    }
+
+    #[test]
+    fn diacritics_and_case() {
+        let document = IdentityDocument::new("ŁUCJA Żaneta", "Łęcka", data::birthdate());
+
+        let result = document.compare(&data::info("Lucja Zaneta", 'L'));
+        check!(result.given_names == FieldMatch::Match);
+        check!(result.surname_initial == FieldMatch::Match);
+
+        let result = document.compare(&data::info("łucja żaneta", 'Ł'));
+        check!(result.given_names == FieldMatch::Match);
+        check!(result.surname_initial == FieldMatch::Match);
+    }
+
+    #[test]
+    fn multiple_given_names() {
+        let document = IdentityDocument::new("Anna Maria", "Kowalska", data::birthdate());
+
+        check!(document.compare(&data::info("Anna", 'K')).given_names == FieldMatch::Partial);
+        check!(document.compare(&data::info("Maria Anna", 'K')).given_names == FieldMatch::Match);
+        check!(document.compare(&data::info("Ewa", 'K')).given_names == FieldMatch::Mismatch);
+        check!(document.compare(&data::info("Anna Ewa", 'K')).given_names == FieldMatch::Mismatch);
+
+        let document = IdentityDocument::new("Anna-Maria", "Kowalska", data::birthdate());
+        check!(document.compare(&data::info("Anna Maria", 'K')).given_names == FieldMatch::Match);
+    }
+
+    #[test]
+    fn mismatch() {
+        let document = IdentityDocument::new("Anna", "Nowak", NaiveDate::from_ymd(1990, 7, 17));
+
+        let result = document.compare(&data::info("Anna", 'K'));
+        check!(result.surname_initial == FieldMatch::Mismatch);
+        check!(result.birthdate == FieldMatch::Mismatch);
+        check!(!result.is_consistent());
+    }
+
+    #[test]
+    fn full_name() {
+        let document =
+            IdentityDocument::from_full_name(" Anna  Maria Kowalska ", data::birthdate());
+        check!(document == IdentityDocument::new("Anna  Maria", "Kowalska", data::birthdate()));
+
+        let document = IdentityDocument::from_full_name("Kowalska", data::birthdate());
+        check!(document == IdentityDocument::new("", "Kowalska", data::birthdate()));
+    }
+}
@@ -0,0 +1,229 @@
+use crate::error::{DecryptionError, KeyringError};
+use crate::vaccination_info::VaccinationInfo;
+use crate::RsaDecrypter;
+use chrono::NaiveDate;
+use std::str::FromStr;
+
+/// Public key trusted for signature verification, optionally limited to a validity window
+pub struct TrustedKey {
+    name: String,
+    decrypter: RsaDecrypter,
+    valid_from: Option<NaiveDate>,
+    valid_until: Option<NaiveDate>,
+}
+
+impl TrustedKey {
+    pub fn new(name: impl Into<String>, decrypter: RsaDecrypter) -> Self {
+        TrustedKey {
+            name: name.into(),
+            decrypter,
+            valid_from: None,
+            valid_until: None,
+        }
+    }
+
+    /// First issue date of the certificates the key is trusted for
+    pub fn valid_from(mut self, date: NaiveDate) -> Self {
+        self.valid_from = Some(date);
+        self
+    }
+
+    /// Last issue date of the certificates the key is trusted for
+    pub fn valid_until(mut self, date: NaiveDate) -> Self {
+        self.valid_until = Some(date);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the key is trusted for certificates issued on `date`
+    pub fn is_valid_on(&self, date: NaiveDate) -> bool {
+        self.valid_from.map_or(true, |from| from <= date)
+            && self.valid_until.map_or(true, |until| date <= until)
+    }
+}
+
+/// Plaintext recovered from a signature, along with the name of the key that matched
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verified {
+    pub plaintext: String,
+    pub key: String,
+}
+
+/// Set of trusted keys; verification tries every key valid on the certificate's issue date
+#[derive(Default)]
+pub struct Keyring {
+    keys: Vec<TrustedKey>,
+}
+
+impl Keyring {
+    pub const DEFAULT_KEY_NAME: &'static str = "publiczny_klucz_podpisu";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keyring with just the built-in ministry key
+    pub fn with_default_key() -> Self {
+        let mut keyring = Self::new();
+
+        keyring.add(TrustedKey::new(
+            Self::DEFAULT_KEY_NAME,
+            RsaDecrypter::default(),
+        ));
+
+        keyring
+    }
+
+    pub fn add(&mut self, key: TrustedKey) -> &mut Self {
+        self.keys.push(key);
+        self
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &TrustedKey> + '_ {
+        self.keys.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Verifies the signature, accepting only a key valid on the certificate's issue date
+    ///
+    /// A key rotated out since still vouches for the certificates it signed while valid.
+    /// If the issue date can't be read, any matching key is accepted; the record is
+    /// malformed anyway.
+    pub fn decrypt(&self, ciphertext: impl AsRef<[u8]>) -> Result<Verified, KeyringError> {
+        let ciphertext = ciphertext.as_ref();
+        let mut errors: Vec<(String, DecryptionError)> = Vec::new();
+        let mut out_of_window = None;
+
+        for key in &self.keys {
+            match key.decrypter.decrypt(ciphertext) {
+                Ok(plaintext) => match issue_date(&plaintext) {
+                    Some(issued) if !key.is_valid_on(issued) => out_of_window = Some(issued),
+                    _ => {
+                        return Ok(Verified {
+                            plaintext,
+                            key: key.name.clone(),
+                        })
+                    }
+                },
+                Err(e) => errors.push((key.name.clone(), e)),
+            }
+        }
+
+        match out_of_window {
+            Some(issued) => Err(KeyringError::NoApplicableKey(issued)),
+            None => Err(KeyringError::NoMatchingKey(errors)),
+        }
+    }
+}
+
+fn issue_date(plaintext: &str) -> Option<NaiveDate> {
+    VaccinationInfo::from_str(plaintext)
+        .ok()
+        .map(|info| info.issue_date())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{check, let_assert};
+
+    mod data {
+        use super::*;
+
+        pub fn test_key() -> TrustedKey {
+            let pem = include_bytes!("../keys/test_public.pem");
+
+            TrustedKey::new(
+                "test",
+                RsaDecrypter::from_pem(&pem[..]).expect("Malformed test RSA key"),
+            )
+        }
+
+        pub fn case1() -> (&'static str, &'static [u8]) {
+            let plain = include_str!("../testdata/1.plain");
+            let cipher = include_bytes!("../testdata/1.cipher");
+
+            (plain, &cipher[..])
+        }
+
+        pub fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+            NaiveDate::from_ymd(y, m, d)
+        }
+    }
+
+    #[test]
+    fn matching_key() {
+        let (plain, cipher) = data::case1();
+
+        let mut keyring = Keyring::with_default_key();
+        keyring.add(data::test_key());
+
+        let_assert!(Ok(verified) = keyring.decrypt(cipher));
+        check!(verified.plaintext == plain);
+        check!(verified.key == "test");
+    }
+
+    #[test]
+    fn no_matching_key() {
+        let (_plain, cipher) = data::case1();
+
+        let keyring = Keyring::with_default_key();
+
+        let_assert!(Err(KeyringError::NoMatchingKey(errors)) = keyring.decrypt(cipher));
+        check!(errors.len() == 1);
+        check!(errors[0].0 == Keyring::DEFAULT_KEY_NAME);
+    }
+
+    #[test]
+    fn validity_window() {
+        let (plain, cipher) = data::case1();
+        // the certificate was issued on 2021-01-20
+        let keyring = |key: TrustedKey| {
+            let mut keyring = Keyring::new();
+            keyring.add(key);
+            keyring
+        };
+
+        let window = keyring(
+            data::test_key()
+                .valid_from(data::date(2021, 1, 1))
+                .valid_until(data::date(2021, 12, 31)),
+        );
+        let_assert!(Ok(verified) = window.decrypt(cipher));
+        check!(verified.plaintext == plain);
+
+        let not_yet = keyring(data::test_key().valid_from(data::date(2021, 1, 21)));
+        let_assert!(Err(KeyringError::NoApplicableKey(issued)) = not_yet.decrypt(cipher));
+        check!(issued == data::date(2021, 1, 20));
+
+        let ended = keyring(data::test_key().valid_until(data::date(2021, 1, 19)));
+        let_assert!(Err(KeyringError::NoApplicableKey(_)) = ended.decrypt(cipher));
+    }
+
+    #[test]
+    fn rotated_out_key() {
+        let (plain, cipher) = data::case1();
+
+        // replaced long ago, but the certificate was issued while the key was in use
+        let mut keyring = Keyring::with_default_key();
+        keyring.add(data::test_key().valid_until(data::date(2021, 6, 30)));
+
+        let_assert!(Ok(verified) = keyring.decrypt(cipher));
+        check!(verified.plaintext == plain);
+        check!(verified.key == "test");
+    }
+
+    #[test]
+    fn empty() {
+        let (_plain, cipher) = data::case1();
+
+        let_assert!(Err(KeyringError::NoMatchingKey(errors)) = Keyring::new().decrypt(cipher));
+        check!(errors.is_empty());
+    }
+}
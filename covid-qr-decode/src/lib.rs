@@ -1,11 +1,40 @@
+mod backend;
+mod clock;
 mod decrypt;
+mod detect;
 pub mod error;
+mod identity;
+mod keyring;
 mod pdf;
+mod pdf_gen;
+mod policy;
+mod preprocess;
 mod qr;
+mod qr_backend;
+mod render;
+mod sign;
 mod vaccination_info;
+mod vaccine;
+mod verify;
 
-pub use crate::pdf::PdfQrExtractor;
+pub use crate::pdf::{PdfImage, PdfQrExtractor};
+pub use crate::pdf_gen::{PdfImageEncoding, PdfQrGenerator};
+pub use backend::{CryptoBackend, MessageSigner, SignatureRecovery};
+pub use clock::{certificate_date, Clock, FixedClock, SystemClock, CERTIFICATE_TIME_ZONE};
 pub use decrypt::RsaDecrypter;
+pub use detect::{
+    decode_as, detect, detect_and_decode, detect_and_decode_with, Candidate, CodeLocation,
+    Confidence, Content, Detected, Detection, InputKind,
+};
+pub use identity::{FieldMatch, IdentityDocument, IdentityMatch};
 pub use image::DynamicImage;
-pub use qr::QrDecoder;
-pub use vaccination_info::VaccinationInfo;
+pub use keyring::{Keyring, TrustedKey, Verified};
+pub use policy::{Policy, PolicyResult, Rule, RuleResult};
+pub use preprocess::{Attempt, Preprocess, Preprocessing};
+pub use qr::{FoundCode, Point, QrDecoder, Scan};
+pub use qr_backend::{DetectedCode, QrBackend, QrDetector};
+pub use render::{ErrorCorrection, QrRenderer};
+pub use sign::RsaSigner;
+pub use vaccination_info::{ShortBirthdate, VaccinationInfo, VaccinationInfoView};
+pub use vaccine::{Vaccine, VaccineRegistry, VaccineType};
+pub use verify::{Stage, Verdict, VerificationError, VerificationResult, Verifier};
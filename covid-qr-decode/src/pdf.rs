@@ -1,101 +1,239 @@
 use crate::error::PdfError;
-use image::DynamicImage;
+use image::{DynamicImage, GrayImage};
 use pdf::file::File;
 use pdf::object::*;
+use pdf::primitive::{Dictionary, PdfStream, Primitive};
+use std::io::Read;
 use std::path::Path;
-use std::rc::Rc;
+use std::sync::{Arc, Mutex, PoisonError};
 
+mod decode;
+mod raster;
+
+/// Image found in a PDF, along with where it was found
+#[derive(Debug)]
+pub struct PdfImage {
+    /// Page number, starting from 1
+    pub page: u32,
+    /// Name of the image XObject in the page resources, or [`PdfQrExtractor::CONTENTS`]
+    /// for the rasterized page drawing
+    pub name: String,
+    pub image: DynamicImage,
+}
+
+/// Extracts the images of a PDF; cheap to clone and safe to share between threads
+///
+/// The file is parsed once, when the extractor is made. Clones share the parsed file.
 #[derive(Clone)]
 pub struct PdfQrExtractor {
-    pdf: Rc<pdf::file::File<Vec<u8>>>,
+    pdf: Arc<Mutex<ParsedPdf>>,
+    pages: u32,
 }
 
+/// Parsed file, only ever used while its lock is held
+struct ParsedPdf(File<Arc<[u8]>>);
+
+// SAFETY: the file is `!Send` because of the `Rc`s in its object cache. They are only
+// cloned and dropped while the mutex is held, and nothing holding them outlives the lock,
+// as everything returned from it must be `Send` itself.
+unsafe impl Send for ParsedPdf {}
+
 impl PdfQrExtractor {
+    /// Image name reported for the vector drawing of a page
+    pub const CONTENTS: &'static str = "Contents";
+
     pub fn with_path(path: impl AsRef<Path>) -> Result<Self, PdfError> {
+        Self::from_vec(std::fs::read(path)?)
+    }
+
+    /// Loads a PDF held in memory, e.g. an upload; see [`from_vec`](Self::from_vec) to avoid a copy
+    pub fn from_bytes(data: &[u8]) -> Result<Self, PdfError> {
+        Self::from_shared(Arc::from(data))
+    }
+
+    pub fn from_vec(data: Vec<u8>) -> Result<Self, PdfError> {
+        Self::from_shared(Arc::from(data))
+    }
+
+    /// Reads the whole PDF before parsing it, since the cross-reference table is at the end
+    pub fn from_reader(mut reader: impl Read) -> Result<Self, PdfError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        Self::from_vec(data)
+    }
+
+    fn from_shared(data: Arc<[u8]>) -> Result<Self, PdfError> {
+        let pdf = File::from_data(data)?;
+        let pages = pdf.num_pages();
+
         Ok(Self {
-            pdf: Rc::new(File::open(path)?),
+            pdf: Arc::new(Mutex::new(ParsedPdf(pdf))),
+            pages,
         })
     }
 
-    pub fn images(&self) -> impl Iterator<Item = Result<DynamicImage, PdfError>> + '_ {
-        self.pdf
-            .pages()
-            .flat_map(|page| page.ok())
-            .flat_map(|page| page.resources().map(|res| res.clone()).ok())
-            .flat_map({
-                move |resources| {
-                    resources
-                        .xobjects
-                        .iter()
-                        .filter_map(|(_name, &r)| self.pdf.get(r).ok())
-                        .filter_map(|object| match *object {
-                            // only images without alpha are supported
-                            XObject::Image(ref img) if img.smask.is_none() => {
-                                Some(Self::image_from_buf(img))
-                            }
-                            _ => None,
-                        })
-                        .collect::<Vec<_>>()
-                        .into_iter()
-                }
-            })
+    pub fn page_count(&self) -> u32 {
+        self.pages
     }
 
-    fn convert_luma1_to_luma8(imdata: &[u8], width: u32) -> Vec<u8> {
-        let row_rem = width % 8;
-        let row_len = width / 8 + if row_rem != 0 { 1 } else { 0 };
+    /// Every image on every page, see [`page_images`](Self::page_images)
+    pub fn images(&self) -> impl Iterator<Item = Result<DynamicImage, PdfError>> + Send + '_ {
+        self.page_images()
+            .map(|image| image.map(|image| image.image))
+    }
 
-        // convert to luma8
-        imdata
-            .chunks(row_len as _)
-            .flat_map(|chunk| {
-                chunk.iter().enumerate().flat_map(|(idx, byte)| {
-                    let is_last = idx == row_len as usize - 1;
+    /// Every image on every page, in page order
+    ///
+    /// Pages and images that fail to load are reported as errors with their location,
+    /// without stopping the iteration. Drawings are left to [`page_drawing`](Self::page_drawing).
+    ///
+    /// Pages are loaded as the iteration reaches them.
+    pub fn page_images(&self) -> impl Iterator<Item = Result<PdfImage, PdfError>> + Send + '_ {
+        (1..=self.pages).flat_map(move |page| self.images_on_page(page))
+    }
 
-                    let upper = if is_last { row_rem } else { 8 };
+    /// Images on a single page, numbered from 1, as reported by [`page_images`](Self::page_images)
+    ///
+    /// Threads take turns loading pages, but can read the QR codes on them in parallel.
+    pub fn images_on_page(&self, page: u32) -> Vec<Result<PdfImage, PdfError>> {
+        // a panic in the pdf crate leaves its cache usable
+        let pdf = self.pdf.lock().unwrap_or_else(PoisonError::into_inner);
 
-                    (0..upper).rev().map(move |pos| {
-                        if byte & (1 << pos) != 0 {
-                            u8::MAX
-                        } else {
-                            u8::MIN
-                        }
-                    })
-                })
-            })
-            .collect::<Vec<u8>>()
+        Self::page_or_error(&pdf.0, page)
     }
 
-    fn image_from_buf(img: &ImageXObject) -> Result<DynamicImage, PdfError> {
-        let buf = img.decode()?;
-        let width = img.width as u32;
-        let height = img.height as u32;
+    /// Rasterized rendering of the filled paths on a page, named [`CONTENTS`](Self::CONTENTS)
+    ///
+    /// Meant for pages whose images hold no QR code, e.g. those printed to PDF by a browser,
+    /// which draws codes as rectangles. `None` if the page draws nothing.
+    pub fn page_drawing(&self, page: u32) -> Option<Result<PdfImage, PdfError>> {
+        let pdf = self.pdf.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let drawing = Self::rasterize_page(&pdf.0, page).transpose()?;
 
-        let luma_buf = if img.bits_per_component == 1 {
-            Self::convert_luma1_to_luma8(&buf, width)
-        } else {
-            buf.to_vec()
+        Some(
+            drawing
+                .map(|image| PdfImage {
+                    page,
+                    name: Self::CONTENTS.to_owned(),
+                    image: DynamicImage::ImageLuma8(image),
+                })
+                .map_err(|e| PdfError::Image {
+                    page,
+                    name: Self::CONTENTS.to_owned(),
+                    source: Box::new(e),
+                }),
+        )
+    }
+
+    fn rasterize_page(pdf: &File<Arc<[u8]>>, number: u32) -> Result<Option<GrayImage>, PdfError> {
+        let page = pdf.get_page(number.wrapping_sub(1))?;
+        let content = match &page.contents {
+            Some(content) => content,
+            None => return Ok(None),
         };
 
-        let imbuf = image::GrayImage::from_raw(width as _, height as _, luma_buf)
-            .ok_or(PdfError::ImageConversion)?;
+        // forms are drawn through the resources, images are skipped
+        let mut xobjects = Dictionary::new();
+        for (name, r) in page.resources()?.xobjects.iter() {
+            xobjects.insert(name.clone(), Primitive::Reference(r.get_inner()));
+        }
 
-        Ok(DynamicImage::ImageLuma8(imbuf))
+        raster::rasterize(content, page.media_box()?, &xobjects, pdf)
+    }
+
+    fn page_or_error(pdf: &File<Arc<[u8]>>, page: u32) -> Vec<Result<PdfImage, PdfError>> {
+        Self::load_page(pdf, page).unwrap_or_else(|e| {
+            vec![Err(PdfError::Page {
+                page,
+                source: Box::new(e),
+            })]
+        })
     }
+
+    // the outer error is for the page itself, the inner ones for its images
+    #[allow(clippy::type_complexity)]
+    fn load_page(
+        pdf: &File<Arc<[u8]>>,
+        number: u32,
+    ) -> Result<Vec<Result<PdfImage, PdfError>>, PdfError> {
+        // page 0 wraps around to a missing page
+        let page = pdf.get_page(number.wrapping_sub(1))?;
+        let resources = page.resources()?;
+
+        // report images in a stable order
+        let mut xobjects: Vec<_> = resources.xobjects.iter().collect();
+        xobjects.sort_by_key(|&(name, _)| name);
+
+        let images = xobjects
+            .into_iter()
+            .filter_map(|(name, &r)| {
+                let image = match pdf.resolve(r.get_inner()) {
+                    Ok(Primitive::Stream(ref stream)) if is_image(stream) => {
+                        decode::decode_image(stream, pdf).map(DynamicImage::ImageLuma8)
+                    }
+                    Ok(_) => return None,
+                    Err(e) => Err(e.into()),
+                };
+
+                Some(
+                    image
+                        .map(|image| PdfImage {
+                            page: number,
+                            name: name.clone(),
+                            image,
+                        })
+                        .map_err(|e| PdfError::Image {
+                            page: number,
+                            name: name.clone(),
+                            source: Box::new(e),
+                        }),
+                )
+            })
+            .collect();
+
+        Ok(images)
+    }
+}
+
+fn is_image(stream: &PdfStream) -> bool {
+    matches!(stream.info.get("Subtype"), Some(Primitive::Name(name)) if name == "Image")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::detect::{decode_as, Content, InputKind};
+    use crate::pdf_gen::PdfBuilder;
+    use crate::{ErrorCorrection, PdfQrGenerator, QrDecoder, QrRenderer};
     use assert2::{check, let_assert};
 
+    mod data {
+        use super::*;
+
+        pub fn image(payload: &str) -> DynamicImage {
+            QrRenderer::new(payload, ErrorCorrection::Medium)
+                .expect("QR code rendering failed")
+                .module_size(3)
+                .to_image()
+        }
+
+        pub fn payloads() -> [&'static str; 2] {
+            [
+                include_str!("../testdata/1.payload"),
+                include_str!("../testdata/2.payload"),
+            ]
+        }
+    }
+
     #[test]
     fn extract_luma1() {
         let_assert!(Ok(pdf) = PdfQrExtractor::with_path("testdata/1.pdf"));
 
         let_assert!(Some(code) = pdf.images().next());
 
-        let_assert!(Ok(code) = code);
+        let_assert!(Ok(_code) = code);
     }
 
     #[test]
@@ -104,6 +242,176 @@ mod tests {
 
         let_assert!(Some(code) = pdf.images().next());
 
-        let_assert!(Ok(code) = code);
+        let_assert!(Ok(_code) = code);
+    }
+
+    #[test]
+    fn page_images() {
+        let_assert!(Ok(pdf) = PdfQrExtractor::with_path("testdata/1.pdf"));
+
+        let images: Vec<_> = pdf.page_images().collect();
+
+        let_assert!([Ok(image)] = &images[..]);
+        check!(image.page == 1);
+        check!(!image.name.is_empty());
+    }
+
+    #[test]
+    fn multiple_pages() {
+        let payloads = data::payloads();
+        let images: Vec<_> = payloads.iter().map(|p| data::image(p)).collect();
+
+        let_assert!(
+            Ok(pdf) = PdfQrExtractor::from_vec(PdfQrGenerator::default().generate_pages(&images))
+        );
+        let images: Vec<_> = pdf.page_images().collect();
+        check!(images.len() == 2);
+
+        let mut qr = QrDecoder::new();
+
+        for (i, (image, payload)) in images.into_iter().zip(&payloads).enumerate() {
+            let_assert!(Ok(image) = image);
+            check!(image.page == i as u32 + 1);
+            check!(image.name == "X0");
+
+            let_assert!(Ok(decoded) = qr.image_get_payload(image.image));
+            check!(&decoded == payload);
+        }
+    }
+
+    #[test]
+    fn image_error() {
+        let [payload, _] = data::payloads();
+        let image = data::image(payload);
+
+        // claim a larger width than the image data has, keeping the xref offsets intact
+        let mut pdf = PdfQrGenerator::default().generate(&image);
+        let width = format!("/Width {}", image.to_luma8().width()).into_bytes();
+        let_assert!(Some(pos) = pdf.windows(width.len()).position(|w| w == &width[..]));
+        for digit in &mut pdf[pos + b"/Width ".len()..pos + width.len()] {
+            *digit = b'9';
+        }
+        let_assert!(Ok(pdf) = PdfQrExtractor::from_vec(pdf));
+        let images: Vec<_> = pdf.page_images().collect();
+
+        let_assert!([Err(PdfError::Image { page, name, source })] = &images[..]);
+        check!(*page == 1);
+        check!(name == "X0");
+        let_assert!(PdfError::ImageConversion = source.as_ref());
+    }
+
+    #[test]
+    fn vector_drawing() {
+        let [payload, _] = data::payloads();
+        let image = QrRenderer::new(payload, ErrorCorrection::Medium)
+            .expect("QR code rendering failed")
+            .module_size(1)
+            .to_image()
+            .to_luma8();
+
+        // one filled rectangle per dark module, as printed by browsers
+        let size = image.height();
+        let mut content = String::from("q 3 0 0 3 0 0 cm 0 g\n");
+        for (x, y, _) in image.enumerate_pixels().filter(|(_, _, p)| p[0] < 128) {
+            content.push_str(&format!("{} {} 1 1 re\n", x, size - 1 - y));
+        }
+        content.push_str("f Q\n");
+
+        let mut builder = PdfBuilder::default();
+        builder.object(b"<</Type/Catalog/Pages 2 0 R>>");
+        builder.object(b"<</Type/Pages/Count 1/Kids[3 0 R]>>");
+        builder.object(
+            format!(
+                "<</Type/Page/Parent 2 0 R/MediaBox[0 0 {size} {size}]/Resources<<>>/Contents 4 0 R>>",
+                size = size * 3
+            )
+            .as_bytes(),
+        );
+        builder.stream(
+            &format!("<</Length {}>>", content.len()),
+            content.as_bytes(),
+        );
+        let data = builder.finish(None);
+        let_assert!(Ok(pdf) = PdfQrExtractor::from_bytes(&data));
+        check!(pdf.page_images().count() == 0);
+
+        let_assert!(Some(Ok(image)) = pdf.page_drawing(1));
+        check!(image.page == 1);
+        check!(image.name == PdfQrExtractor::CONTENTS);
+
+        let_assert!(Ok(decoded) = QrDecoder::new().image_get_payload(image.image.clone()));
+        check!(decoded == payload);
+
+        // pages without a code in their images fall back to the drawing
+        let_assert!(
+            Ok(Content::Ciphertext(candidates)) =
+                decode_as(InputKind::Pdf, &data, &mut QrDecoder::new())
+        );
+        let_assert!([candidate] = &candidates[..]);
+        let_assert!(Some(location) = &candidate.location);
+        check!(location.image.as_deref() == Some(PdfQrExtractor::CONTENTS));
+    }
+
+    #[test]
+    fn from_memory() {
+        let data = std::fs::read("testdata/1.pdf").expect("Unable to read test data");
+
+        let_assert!(Ok(pdf) = PdfQrExtractor::from_bytes(&data));
+        check!(pdf.images().count() == 1);
+
+        let_assert!(Ok(pdf) = PdfQrExtractor::from_reader(&data[..]));
+        check!(pdf.images().count() == 1);
+
+        let_assert!(Ok(pdf) = PdfQrExtractor::from_vec(data));
+        let_assert!(Some(Ok(_image)) = pdf.images().next());
+
+        let_assert!(Err(PdfError::PdfProcessing(_)) = PdfQrExtractor::from_bytes(b"not a PDF"));
+    }
+
+    #[test]
+    fn parallel_pages() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let payloads = data::payloads();
+        let images: Vec<_> = payloads.iter().map(|p| data::image(p)).collect();
+
+        let_assert!(
+            Ok(pdf) = PdfQrExtractor::from_vec(PdfQrGenerator::default().generate_pages(&images))
+        );
+        assert_send_sync(&pdf);
+        check!(pdf.page_count() == 2);
+
+        let decoded: Vec<_> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (1..=pdf.page_count())
+                .map(|page| {
+                    let pdf = &pdf;
+                    scope.spawn(move || {
+                        let images = pdf.images_on_page(page);
+                        let_assert!([Ok(image)] = &images[..]);
+                        QrDecoder::new().image_get_payload(image.image.clone())
+                    })
+                })
+                .collect();
+
+            workers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+
+        let_assert!([Ok(first), Ok(second)] = &decoded[..]);
+        check!([first.as_str(), second.as_str()] == payloads);
+
+        // the iterator can be moved to another thread as well
+        let images = std::thread::spawn(move || pdf.images().count());
+        let_assert!(Ok(2) = images.join());
+    }
+
+    #[test]
+    fn missing_page() {
+        let_assert!(Ok(pdf) = PdfQrExtractor::with_path("testdata/1.pdf"));
+
+        for page in [0, 2] {
+            let images = pdf.images_on_page(page);
+            let_assert!([Err(PdfError::Page { page: number, .. })] = &images[..]);
+            check!(*number == page);
+        }
     }
 }
@@ -0,0 +1,657 @@
+use crate::error::PdfError;
+use image::{imageops, GrayImage, Luma};
+use pdf::object::{Resolve, Stream};
+use pdf::primitive::{Dictionary, PdfStream, Primitive};
+
+/// Color space of an image, reduced to what's needed for the conversion to grayscale
+enum ColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    /// Single colorant, e.g. a spot color; 1 is full ink
+    Separation,
+    /// Palette entries already converted to luma
+    Indexed(Vec<f32>),
+}
+
+impl ColorSpace {
+    fn components(&self) -> usize {
+        match self {
+            ColorSpace::Gray | ColorSpace::Separation | ColorSpace::Indexed(_) => 1,
+            ColorSpace::Rgb => 3,
+            ColorSpace::Cmyk => 4,
+        }
+    }
+
+    /// Default `Decode` array entry, for a component with `max` as the largest sample value
+    fn decode_range(&self, max: f32) -> (f32, f32) {
+        match self {
+            ColorSpace::Indexed(_) => (0.0, max),
+            _ => (0.0, 1.0),
+        }
+    }
+
+    /// Luma in the 0-1 range; components are in the color space's own range
+    fn luma(&self, c: &[f32]) -> f32 {
+        match self {
+            ColorSpace::Gray => c[0],
+            ColorSpace::Rgb => rgb_luma(c[0], c[1], c[2]),
+            ColorSpace::Cmyk => {
+                let k = 1.0 - c[3];
+                rgb_luma((1.0 - c[0]) * k, (1.0 - c[1]) * k, (1.0 - c[2]) * k)
+            }
+            ColorSpace::Separation => 1.0 - c[0],
+            ColorSpace::Indexed(palette) => {
+                let index = (c[0].round().max(0.0) as usize).min(palette.len() - 1);
+                palette[index]
+            }
+        }
+    }
+
+    fn from_primitive(p: &Primitive, resolve: &impl Resolve) -> Result<Self, PdfError> {
+        let p = resolved(p, resolve)?;
+
+        let (family, params) = match &p {
+            Primitive::Name(name) => (name.clone(), &[][..]),
+            Primitive::Array(array) => match array.split_first() {
+                Some((family, params)) => {
+                    (resolved(family, resolve)?.as_name()?.to_owned(), params)
+                }
+                None => return Err(PdfError::UnsupportedColorSpace("[]".to_owned())),
+            },
+            other => return Err(PdfError::UnsupportedColorSpace(other.to_string())),
+        };
+
+        match family.as_str() {
+            "DeviceGray" | "CalGray" => Ok(ColorSpace::Gray),
+            "DeviceRGB" | "CalRGB" => Ok(ColorSpace::Rgb),
+            "DeviceCMYK" => Ok(ColorSpace::Cmyk),
+            "Separation" => Ok(ColorSpace::Separation),
+            "ICCBased" => {
+                // the profile itself is ignored, only the number of components matters
+                let profile = params
+                    .first()
+                    .ok_or_else(|| PdfError::UnsupportedColorSpace(family.clone()))?;
+                let profile = resolved(profile, resolve)?.into_stream(resolve)?;
+
+                match integer(profile.info.get("N"), resolve)? {
+                    Some(1) => Ok(ColorSpace::Gray),
+                    Some(3) => Ok(ColorSpace::Rgb),
+                    Some(4) => Ok(ColorSpace::Cmyk),
+                    _ => Err(PdfError::UnsupportedColorSpace(family.clone())),
+                }
+            }
+            "Indexed" => {
+                let (base, hival, lookup) = match params {
+                    [base, hival, lookup] => (base, hival, lookup),
+                    _ => return Err(PdfError::UnsupportedColorSpace(family.clone())),
+                };
+
+                let base = ColorSpace::from_primitive(base, resolve)?;
+                let hival = integer(Some(hival), resolve)?.unwrap_or(0).max(0) as usize;
+                let lookup = match resolved(lookup, resolve)? {
+                    Primitive::String(s) => s.into_bytes(),
+                    Primitive::Stream(s) => Stream::<()>::from_stream(s, resolve)?
+                        .decode()?
+                        .into_owned(),
+                    other => return Err(PdfError::UnsupportedColorSpace(other.to_string())),
+                };
+
+                let n = base.components();
+                if lookup.len() < (hival + 1) * n {
+                    return Err(PdfError::ImageConversion);
+                }
+
+                let palette = lookup
+                    .chunks(n)
+                    .take(hival + 1)
+                    .map(|entry| {
+                        let c: Vec<f32> = entry.iter().map(|&b| b as f32 / 255.0).collect();
+                        base.luma(&c)
+                    })
+                    .collect();
+
+                Ok(ColorSpace::Indexed(palette))
+            }
+            other => Err(PdfError::UnsupportedColorSpace(other.to_owned())),
+        }
+    }
+}
+
+fn rgb_luma(r: f32, g: f32, b: f32) -> f32 {
+    0.299 * r + 0.587 * g + 0.114 * b
+}
+
+fn resolved(p: &Primitive, resolve: &impl Resolve) -> Result<Primitive, PdfError> {
+    match p {
+        Primitive::Reference(r) => Ok(resolve.resolve(*r)?),
+        p => Ok(p.clone()),
+    }
+}
+
+fn integer(p: Option<&Primitive>, resolve: &impl Resolve) -> Result<Option<i32>, PdfError> {
+    match p {
+        Some(p) => Ok(Some(resolved(p, resolve)?.as_integer()?)),
+        None => Ok(None),
+    }
+}
+
+fn boolean(dict: &Dictionary, key: &str) -> bool {
+    matches!(dict.get(key), Some(Primitive::Boolean(true)))
+}
+
+/// Dictionary entry that may hold either a single value or an array of them
+fn one_or_many(p: Option<&Primitive>, resolve: &impl Resolve) -> Result<Vec<Primitive>, PdfError> {
+    match p.map(|p| resolved(p, resolve)).transpose()? {
+        None | Some(Primitive::Null) => Ok(Vec::new()),
+        Some(Primitive::Array(array)) => array.iter().map(|p| resolved(p, resolve)).collect(),
+        Some(p) => Ok(vec![p]),
+    }
+}
+
+/// Filters applied to the stream, with their parameters
+fn filters(
+    dict: &Dictionary,
+    resolve: &impl Resolve,
+) -> Result<Vec<(String, Option<Dictionary>)>, PdfError> {
+    let names = one_or_many(dict.get("Filter"), resolve)?;
+    let params = one_or_many(dict.get("DecodeParms"), resolve)?;
+
+    names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let name = name.as_name()?.to_owned();
+            let params = match params.get(i) {
+                Some(Primitive::Dictionary(params)) => Some(params.clone()),
+                _ => None,
+            };
+
+            Ok((name, params))
+        })
+        .collect()
+}
+
+/// Reads `bits` wide samples from a row; rows always start on a byte boundary
+fn sample(row: &[u8], index: usize, bits: usize) -> u16 {
+    match bits {
+        16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+        8 => row[index] as u16,
+        _ => {
+            let bit = index * bits;
+            let byte = row[bit / 8];
+            let shift = 8 - bits - bit % 8;
+
+            ((byte >> shift) & ((1 << bits) - 1) as u8) as u16
+        }
+    }
+}
+
+/// Converts an image XObject to grayscale, compositing it over white if it has a soft mask
+pub(super) fn decode_image(
+    stream: &PdfStream,
+    resolve: &impl Resolve,
+) -> Result<GrayImage, PdfError> {
+    let mut image = decode_samples(stream, resolve)?;
+
+    if let Some(smask) = stream.info.get("SMask") {
+        // soft masks can't have masks of their own, which also rules out cycles
+        let smask = match resolved(smask, resolve)? {
+            Primitive::Stream(smask) => decode_samples(&smask, resolve)?,
+            Primitive::Null => return Ok(image),
+            _ => return Err(PdfError::ImageConversion),
+        };
+
+        composite_over_white(&mut image, &smask);
+    }
+
+    Ok(image)
+}
+
+/// Converts the image samples to grayscale, ignoring any soft mask
+fn decode_samples(stream: &PdfStream, resolve: &impl Resolve) -> Result<GrayImage, PdfError> {
+    let dict = &stream.info;
+
+    let width = integer(dict.get("Width"), resolve)?.ok_or(PdfError::ImageConversion)?;
+    let height = integer(dict.get("Height"), resolve)?.ok_or(PdfError::ImageConversion)?;
+    if width <= 0 || height <= 0 {
+        return Err(PdfError::ImageConversion);
+    }
+    let (width, height) = (width as u32, height as u32);
+
+    let filters = filters(dict, resolve)?;
+    let last_filter = filters.last().map(|(name, _)| name.as_str());
+
+    // the pdf crate panics on these
+    for (name, params) in &filters {
+        match name.as_str() {
+            "FlateDecode" | "LZWDecode" | "ASCIIHexDecode" | "ASCII85Decode" | "DCTDecode" => {}
+            "CCITTFaxDecode" => {
+                // only Group 4 is implemented, K >= 0 is Group 3
+                let k = integer(params.as_ref().and_then(|p| p.get("K")), resolve)?.unwrap_or(0);
+                if k >= 0 {
+                    return Err(PdfError::UnsupportedFilter(format!(
+                        "{} Group 3 (K={})",
+                        name, k
+                    )));
+                }
+            }
+            other => return Err(PdfError::UnsupportedFilter(other.to_owned())),
+        }
+    }
+
+    let stream = Stream::<()>::from_stream(stream.clone(), resolve)?;
+    let data = stream.decode()?;
+
+    let image_mask = boolean(dict, "ImageMask");
+    let color_space = match dict.get("ColorSpace") {
+        // stencil masks paint with the fill color, assumed black, where samples are 0
+        _ if image_mask => ColorSpace::Gray,
+        Some(p) => ColorSpace::from_primitive(p, resolve)?,
+        // JPEG data carries its own component count
+        None if last_filter == Some("DCTDecode") => {
+            match data.len() / (width as usize * height as usize) {
+                1 => ColorSpace::Gray,
+                3 => ColorSpace::Rgb,
+                4 => ColorSpace::Cmyk,
+                _ => return Err(PdfError::ImageConversion),
+            }
+        }
+        None => return Err(PdfError::UnsupportedColorSpace("none".to_owned())),
+    };
+
+    // JPEG and fax decoders already produce 8 bit samples
+    let bits = match last_filter {
+        Some("DCTDecode") | Some("CCITTFaxDecode") => 8,
+        _ if image_mask => 1,
+        _ => integer(dict.get("BitsPerComponent"), resolve)?.unwrap_or(8),
+    };
+    if ![1, 2, 4, 8, 16].contains(&bits) {
+        return Err(PdfError::ImageConversion);
+    }
+    let bits = bits as usize;
+    let max = ((1u32 << bits) - 1) as f32;
+
+    let components = color_space.components();
+
+    let mut decode: Vec<(f32, f32)> = match one_or_many(dict.get("Decode"), resolve)? {
+        array if array.len() == components * 2 => array
+            .chunks(2)
+            .map(|pair| Ok((pair[0].as_number()?, pair[1].as_number()?)))
+            .collect::<Result<_, PdfError>>()?,
+        _ => vec![color_space.decode_range(max); components],
+    };
+
+    // fax data with 1 meaning black
+    let black_is_1 = filters.iter().any(|(name, params)| {
+        name == "CCITTFaxDecode" && params.as_ref().is_some_and(|p| boolean(p, "BlackIs1"))
+    });
+    if black_is_1 {
+        decode = decode.into_iter().map(|(min, max)| (max, min)).collect();
+    }
+
+    let row_len = (width as usize * components * bits).div_ceil(8);
+    if data.len() < row_len * height as usize {
+        return Err(PdfError::ImageConversion);
+    }
+
+    let mut c = vec![0.0; components];
+    let image = GrayImage::from_fn(width, height, |x, y| {
+        let row = &data[y as usize * row_len..][..row_len];
+
+        for (i, (c, &(dmin, dmax))) in c.iter_mut().zip(&decode).enumerate() {
+            let raw = sample(row, x as usize * components + i, bits) as f32;
+            *c = dmin + raw * (dmax - dmin) / max;
+        }
+
+        Luma([(color_space.luma(&c).clamp(0.0, 1.0) * 255.0).round() as u8])
+    });
+
+    Ok(image)
+}
+
+/// Blends the image over a white background, using the soft mask as alpha
+fn composite_over_white(image: &mut GrayImage, alpha: &GrayImage) {
+    let (width, height) = image.dimensions();
+
+    // the mask may have a different resolution than the image
+    let alpha = if alpha.dimensions() == (width, height) {
+        alpha.clone()
+    } else {
+        imageops::resize(alpha, width, height, imageops::FilterType::Triangle)
+    };
+
+    for (pixel, a) in image.pixels_mut().zip(alpha.pixels()) {
+        let (v, a) = (pixel[0] as u32, a[0] as u32);
+
+        pixel[0] = ((v * a + 255 * (255 - a) + 127) / 255) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ErrorCorrection, QrDecoder, QrRenderer};
+    use assert2::{check, let_assert};
+    use image::DynamicImage;
+    use pdf::object::NoResolve;
+    use pdf::primitive::PdfString;
+
+    mod data {
+        use super::*;
+
+        pub fn payload() -> &'static str {
+            include_str!("../../testdata/1.payload")
+        }
+
+        pub fn image() -> GrayImage {
+            QrRenderer::new(payload(), ErrorCorrection::Medium)
+                .expect("QR code rendering failed")
+                .module_size(3)
+                .to_image()
+                .to_luma8()
+        }
+
+        pub fn stream(
+            image: &GrayImage,
+            entries: Vec<(&str, Primitive)>,
+            data: Vec<u8>,
+        ) -> PdfStream {
+            let mut info = Dictionary::new();
+            info.insert("Type", Primitive::name("XObject"));
+            info.insert("Subtype", Primitive::name("Image"));
+            info.insert("Width", Primitive::Integer(image.width() as i32));
+            info.insert("Height", Primitive::Integer(image.height() as i32));
+            info.insert("Length", Primitive::Integer(data.len() as i32));
+            for (key, value) in entries {
+                info.insert(key, value);
+            }
+
+            PdfStream { info, data }
+        }
+
+        /// `image()` compressed with CCITT Group 4
+        pub fn fax() -> &'static [u8] {
+            include_bytes!("../../testdata/1.g4")
+        }
+
+        pub fn names(names: &[&str]) -> Primitive {
+            Primitive::Array(names.iter().map(|&name| Primitive::name(name)).collect())
+        }
+    }
+
+    #[test]
+    fn rgb() {
+        let image = data::image();
+        let rgb = DynamicImage::ImageLuma8(image.clone()).to_rgb8().into_raw();
+
+        let stream = data::stream(
+            &image,
+            vec![
+                ("ColorSpace", Primitive::name("DeviceRGB")),
+                ("BitsPerComponent", Primitive::Integer(8)),
+            ],
+            rgb,
+        );
+
+        let_assert!(Ok(decoded) = decode_image(&stream, &NoResolve));
+        check!(decoded == image);
+    }
+
+    #[test]
+    fn cmyk() {
+        let image = data::image();
+        let cmyk = image
+            .pixels()
+            .flat_map(|p| vec![0, 0, 0, 255 - p[0]])
+            .collect();
+
+        let stream = data::stream(
+            &image,
+            vec![
+                ("ColorSpace", Primitive::name("DeviceCMYK")),
+                ("BitsPerComponent", Primitive::Integer(8)),
+            ],
+            cmyk,
+        );
+
+        let_assert!(Ok(decoded) = decode_image(&stream, &NoResolve));
+        check!(decoded == image);
+    }
+
+    #[test]
+    fn indexed() {
+        let image = data::image();
+        // 2 bit indices into a white, red, black palette, with rows padded to a byte
+        let indices: Vec<u8> = image
+            .rows()
+            .flat_map(|row| {
+                let row: Vec<u8> = row.map(|p| if p[0] < 128 { 2 } else { 0 }).collect();
+                row.chunks(4)
+                    .map(|chunk| {
+                        chunk
+                            .iter()
+                            .enumerate()
+                            .fold(0, |byte, (i, index)| byte | index << (6 - 2 * i))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let palette = vec![255, 255, 255, 255, 0, 0, 0, 0, 0];
+
+        let stream = data::stream(
+            &image,
+            vec![
+                (
+                    "ColorSpace",
+                    Primitive::Array(vec![
+                        Primitive::name("Indexed"),
+                        Primitive::name("DeviceRGB"),
+                        Primitive::Integer(2),
+                        Primitive::String(PdfString::new(palette)),
+                    ]),
+                ),
+                ("BitsPerComponent", Primitive::Integer(2)),
+            ],
+            indices,
+        );
+
+        let_assert!(Ok(decoded) = decode_image(&stream, &NoResolve));
+        check!(decoded == image);
+    }
+
+    #[test]
+    fn decode_array() {
+        let image = data::image();
+        let mut inverted = image.clone();
+        image::imageops::invert(&mut inverted);
+
+        let stream = data::stream(
+            &image,
+            vec![
+                ("ColorSpace", Primitive::name("DeviceGray")),
+                ("BitsPerComponent", Primitive::Integer(8)),
+                (
+                    "Decode",
+                    Primitive::Array(vec![Primitive::Integer(1), Primitive::Integer(0)]),
+                ),
+            ],
+            inverted.into_raw(),
+        );
+
+        let_assert!(Ok(decoded) = decode_image(&stream, &NoResolve));
+        check!(decoded == image);
+    }
+
+    #[test]
+    fn inverted_bitmap() {
+        let image = data::image();
+        // 1 bits are black, rows padded to whole bytes
+        let row_len = (image.width() as usize).div_ceil(8);
+        let mut bitmap = vec![0u8; row_len * image.height() as usize];
+        for (x, y, pixel) in image.enumerate_pixels() {
+            if pixel[0] < 128 {
+                bitmap[y as usize * row_len + x as usize / 8] |= 0x80 >> (x % 8);
+            }
+        }
+
+        let stream = data::stream(
+            &image,
+            vec![
+                ("ColorSpace", Primitive::name("DeviceGray")),
+                ("BitsPerComponent", Primitive::Integer(1)),
+                (
+                    "Decode",
+                    Primitive::Array(vec![Primitive::Integer(1), Primitive::Integer(0)]),
+                ),
+            ],
+            bitmap,
+        );
+
+        let_assert!(Ok(decoded) = decode_image(&stream, &NoResolve));
+        check!(decoded == image);
+    }
+
+    #[test]
+    fn fax() {
+        let image = data::image();
+        let stream = |black_is_1: bool| {
+            let mut params = Dictionary::new();
+            params.insert("K", Primitive::Integer(-1));
+            params.insert("Columns", Primitive::Integer(image.width() as i32));
+            params.insert("Rows", Primitive::Integer(image.height() as i32));
+            params.insert("BlackIs1", Primitive::Boolean(black_is_1));
+
+            data::stream(
+                &image,
+                vec![
+                    ("Filter", Primitive::name("CCITTFaxDecode")),
+                    ("DecodeParms", Primitive::Dictionary(params)),
+                    ("ColorSpace", Primitive::name("DeviceGray")),
+                    ("BitsPerComponent", Primitive::Integer(1)),
+                ],
+                data::fax().to_vec(),
+            )
+        };
+
+        let_assert!(Ok(decoded) = decode_image(&stream(false), &NoResolve));
+        check!(decoded == image);
+
+        let mut inverted = image.clone();
+        image::imageops::invert(&mut inverted);
+        let_assert!(Ok(decoded) = decode_image(&stream(true), &NoResolve));
+        check!(decoded == inverted);
+    }
+
+    #[test]
+    fn soft_mask() {
+        let image = data::image();
+        // black ink, only opaque where the code is dark
+        let mut alpha = image.clone();
+        image::imageops::invert(&mut alpha);
+        // masks of masks are ignored, so crafted chains can't recurse
+        let nested = data::stream(
+            &image,
+            vec![
+                ("ColorSpace", Primitive::name("DeviceGray")),
+                ("BitsPerComponent", Primitive::Integer(8)),
+            ],
+            vec![0; image.as_raw().len()],
+        );
+        let smask = data::stream(
+            &image,
+            vec![
+                ("ColorSpace", Primitive::name("DeviceGray")),
+                ("BitsPerComponent", Primitive::Integer(8)),
+                ("SMask", Primitive::Stream(nested)),
+            ],
+            alpha.into_raw(),
+        );
+
+        let stream = data::stream(
+            &image,
+            vec![
+                ("ColorSpace", Primitive::name("DeviceGray")),
+                ("BitsPerComponent", Primitive::Integer(8)),
+                ("SMask", Primitive::Stream(smask)),
+            ],
+            vec![0; image.as_raw().len()],
+        );
+
+        let_assert!(Ok(decoded) = decode_image(&stream, &NoResolve));
+        check!(decoded == image);
+    }
+
+    #[test]
+    fn jpeg() {
+        let image = data::image();
+        let mut jpeg = Vec::new();
+        let_assert!(
+            Ok(()) = image::codecs::jpeg::JpegEncoder::new(&mut jpeg).encode(
+                image.as_raw(),
+                image.width(),
+                image.height(),
+                image::ColorType::L8,
+            )
+        );
+
+        // the color space is left for the decoder to figure out
+        let stream = data::stream(
+            &image,
+            vec![
+                ("Filter", Primitive::name("DCTDecode")),
+                ("BitsPerComponent", Primitive::Integer(8)),
+            ],
+            jpeg,
+        );
+
+        let_assert!(Ok(decoded) = decode_image(&stream, &NoResolve));
+        let_assert!(
+            Ok(payload) = QrDecoder::new().image_get_payload(DynamicImage::ImageLuma8(decoded))
+        );
+        check!(payload == data::payload());
+    }
+
+    #[test]
+    fn unsupported_filter() {
+        let image = data::image();
+
+        let stream = data::stream(
+            &image,
+            vec![("Filter", data::names(&["FlateDecode", "JPXDecode"]))],
+            Vec::new(),
+        );
+        let_assert!(Err(PdfError::UnsupportedFilter(filter)) = decode_image(&stream, &NoResolve));
+        check!(filter == "JPXDecode");
+
+        // only Group 4 fax data can be decoded
+        let mut params = Dictionary::new();
+        params.insert("K", Primitive::Integer(0));
+        let stream = data::stream(
+            &image,
+            vec![
+                ("Filter", Primitive::name("CCITTFaxDecode")),
+                ("DecodeParms", Primitive::Dictionary(params)),
+            ],
+            Vec::new(),
+        );
+        let_assert!(Err(PdfError::UnsupportedFilter(filter)) = decode_image(&stream, &NoResolve));
+        check!(filter == "CCITTFaxDecode Group 3 (K=0)");
+    }
+
+    #[test]
+    fn unsupported_color_space() {
+        let image = data::image();
+
+        let stream = data::stream(
+            &image,
+            vec![("ColorSpace", data::names(&["Lab"]))],
+            image.as_raw().clone(),
+        );
+
+        let_assert!(
+            Err(PdfError::UnsupportedColorSpace(space)) = decode_image(&stream, &NoResolve)
+        );
+        check!(space == "Lab");
+    }
+}
@@ -0,0 +1,535 @@
+use crate::error::PdfError;
+use image::{GrayImage, Luma};
+use pdf::content::{Content, Operation};
+use pdf::object::{Object, Rect, Resolve};
+use pdf::primitive::{Dictionary, Primitive};
+
+/// Size of the longer side of the rasterized page, in pixels
+const MAX_DIMENSION: f32 = 1600.0;
+/// Limit on nested form XObjects, which may also be self-referencing
+const MAX_DEPTH: u32 = 8;
+/// Line segments per Bézier curve
+const CURVE_SEGMENTS: usize = 16;
+
+/// Affine transformation `[a b c d e f]`, as in the `cm` operator
+#[derive(Debug, Copy, Clone)]
+struct Matrix([f32; 6]);
+
+impl Matrix {
+    const IDENTITY: Matrix = Matrix([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    fn from_operands(operands: &[Primitive]) -> Option<Self> {
+        let mut m = [0.0; 6];
+        if operands.len() != m.len() {
+            return None;
+        }
+
+        for (m, operand) in m.iter_mut().zip(operands) {
+            *m = operand.as_number().ok()?;
+        }
+
+        Some(Matrix(m))
+    }
+
+    /// Transformation applying `self` first, then `other`
+    fn then(self, other: Matrix) -> Matrix {
+        let [a, b, c, d, e, f] = self.0;
+        let [a2, b2, c2, d2, e2, f2] = other.0;
+
+        Matrix([
+            a * a2 + b * c2,
+            a * b2 + b * d2,
+            c * a2 + d * c2,
+            c * b2 + d * d2,
+            e * a2 + f * c2 + e2,
+            e * b2 + f * d2 + f2,
+        ])
+    }
+
+    fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        let [a, b, c, d, e, f] = self.0;
+
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct State {
+    ctm: Matrix,
+    fill: u8,
+}
+
+/// Fill color operands as luma, for any of the device color spaces
+fn fill_luma(operands: &[Primitive]) -> Option<u8> {
+    let c = operands
+        .iter()
+        .map(|o| o.as_number().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    let luma = match c[..] {
+        [gray] => gray,
+        [r, g, b] => 0.299 * r + 0.587 * g + 0.114 * b,
+        [c, m, y, k] => {
+            let k = 1.0 - k;
+            0.299 * (1.0 - c) * k + 0.587 * (1.0 - m) * k + 0.114 * (1.0 - y) * k
+        }
+        _ => return None,
+    };
+
+    Some((luma.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+/// Renders the filled paths of a content stream, ignoring text, images and strokes
+struct Rasterizer<'a, R> {
+    canvas: GrayImage,
+    resolve: &'a R,
+    /// Whether anything dark was drawn
+    inked: bool,
+}
+
+impl<'a, R: Resolve> Rasterizer<'a, R> {
+    fn run(
+        &mut self,
+        operations: &[Operation],
+        mut state: State,
+        xobjects: &Dictionary,
+        depth: u32,
+    ) -> Result<(), PdfError> {
+        let mut stack = Vec::new();
+        // subpaths in device space
+        let mut path: Vec<Vec<(f32, f32)>> = Vec::new();
+        let mut current = (0.0, 0.0);
+
+        for op in operations {
+            let n: Vec<f32> = op
+                .operands
+                .iter()
+                .filter_map(|o| o.as_number().ok())
+                .collect();
+
+            match (op.operator.as_str(), &n[..]) {
+                ("q", _) => stack.push(state),
+                ("Q", _) => state = stack.pop().unwrap_or(state),
+                ("cm", _) => {
+                    if let Some(m) = Matrix::from_operands(&op.operands) {
+                        state.ctm = m.then(state.ctm);
+                    }
+                }
+                ("g", _) | ("rg", _) | ("k", _) | ("sc", _) | ("scn", _) => {
+                    if let Some(fill) = fill_luma(&op.operands) {
+                        state.fill = fill;
+                    }
+                }
+                ("m", &[x, y]) => {
+                    current = (x, y);
+                    path.push(vec![state.ctm.apply(x, y)]);
+                }
+                ("l", &[x, y]) => {
+                    current = (x, y);
+                    Self::line_to(&mut path, state.ctm.apply(x, y));
+                }
+                ("c", &[x1, y1, x2, y2, x3, y3]) => {
+                    Self::curve_to(&mut path, state.ctm, current, (x1, y1), (x2, y2), (x3, y3));
+                    current = (x3, y3);
+                }
+                ("v", &[x2, y2, x3, y3]) => {
+                    Self::curve_to(&mut path, state.ctm, current, current, (x2, y2), (x3, y3));
+                    current = (x3, y3);
+                }
+                ("y", &[x1, y1, x3, y3]) => {
+                    Self::curve_to(&mut path, state.ctm, current, (x1, y1), (x3, y3), (x3, y3));
+                    current = (x3, y3);
+                }
+                ("re", &[x, y, w, h]) => {
+                    current = (x, y);
+                    path.push(
+                        [(x, y), (x + w, y), (x + w, y + h), (x, y + h)]
+                            .iter()
+                            .map(|&(x, y)| state.ctm.apply(x, y))
+                            .collect(),
+                    );
+                }
+                ("f", _) | ("F", _) | ("B", _) | ("b", _) => {
+                    self.fill(&path, false, state.fill);
+                    path.clear();
+                }
+                ("f*", _) | ("B*", _) | ("b*", _) => {
+                    self.fill(&path, true, state.fill);
+                    path.clear();
+                }
+                ("S", _) | ("s", _) | ("n", _) => path.clear(),
+                ("Do", _) if depth < MAX_DEPTH => {
+                    if let Some(Primitive::Name(name)) = op.operands.first() {
+                        self.form(name, state, xobjects, depth)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn line_to(path: &mut Vec<Vec<(f32, f32)>>, point: (f32, f32)) {
+        match path.last_mut() {
+            Some(subpath) => subpath.push(point),
+            None => path.push(vec![point]),
+        }
+    }
+
+    fn curve_to(
+        path: &mut Vec<Vec<(f32, f32)>>,
+        ctm: Matrix,
+        p0: (f32, f32),
+        p1: (f32, f32),
+        p2: (f32, f32),
+        p3: (f32, f32),
+    ) {
+        for i in 1..=CURVE_SEGMENTS {
+            let t = i as f32 / CURVE_SEGMENTS as f32;
+            let u = 1.0 - t;
+            let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+
+            let x = a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0;
+            let y = a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1;
+            Self::line_to(path, ctm.apply(x, y));
+        }
+    }
+
+    /// Draws a form XObject; images are left to [`super::PdfQrExtractor::images_on_page`]
+    fn form(
+        &mut self,
+        name: &str,
+        state: State,
+        xobjects: &Dictionary,
+        depth: u32,
+    ) -> Result<(), PdfError> {
+        let stream = match xobjects.get(name).map(|p| self.resolved(p)).transpose()? {
+            Some(Primitive::Stream(stream)) => stream,
+            _ => return Ok(()),
+        };
+
+        if !matches!(stream.info.get("Subtype"), Some(Primitive::Name(s)) if s == "Form") {
+            return Ok(());
+        }
+
+        let matrix = match stream
+            .info
+            .get("Matrix")
+            .map(|p| self.resolved(p))
+            .transpose()?
+        {
+            Some(Primitive::Array(m)) => Matrix::from_operands(&m).unwrap_or(Matrix::IDENTITY),
+            _ => Matrix::IDENTITY,
+        };
+
+        // forms without resources of their own use the ones of the page
+        let own_xobjects = match stream
+            .info
+            .get("Resources")
+            .map(|p| self.resolved(p))
+            .transpose()?
+        {
+            Some(Primitive::Dictionary(resources)) => {
+                match resources
+                    .get("XObject")
+                    .map(|p| self.resolved(p))
+                    .transpose()?
+                {
+                    Some(Primitive::Dictionary(xobjects)) => Some(xobjects),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        let content = Content::from_primitive(Primitive::Stream(stream), self.resolve)?;
+        let state = State {
+            ctm: matrix.then(state.ctm),
+            ..state
+        };
+
+        self.run(
+            &content.operations,
+            state,
+            own_xobjects.as_ref().unwrap_or(xobjects),
+            depth + 1,
+        )
+    }
+
+    fn resolved(&self, p: &Primitive) -> Result<Primitive, PdfError> {
+        match p {
+            Primitive::Reference(r) => Ok(self.resolve.resolve(*r)?),
+            p => Ok(p.clone()),
+        }
+    }
+
+    /// Scanline polygon fill, sampling at pixel centers
+    fn fill(&mut self, path: &[Vec<(f32, f32)>], even_odd: bool, luma: u8) {
+        // subpaths are implicitly closed; edges carry their winding direction
+        let edges: Vec<_> = path
+            .iter()
+            .flat_map(|subpath| {
+                subpath
+                    .iter()
+                    .zip(subpath.iter().cycle().skip(1))
+                    .filter(|(a, b)| a.1 != b.1)
+                    .map(|(&a, &b)| if a.1 < b.1 { (a, b, 1) } else { (b, a, -1) })
+            })
+            .collect();
+
+        let (width, height) = self.canvas.dimensions();
+        let mut crossings = Vec::new();
+
+        for py in 0..height {
+            let y = py as f32 + 0.5;
+
+            crossings.clear();
+            crossings.extend(
+                edges
+                    .iter()
+                    .filter(|(a, b, _)| a.1 <= y && y < b.1)
+                    .map(|&(a, b, winding)| (a.0 + (y - a.1) * (b.0 - a.0) / (b.1 - a.1), winding)),
+            );
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut inside = 0;
+            for pair in crossings.windows(2) {
+                inside += pair[0].1;
+
+                let filled = if even_odd {
+                    inside % 2 != 0
+                } else {
+                    inside != 0
+                };
+                if !filled {
+                    continue;
+                }
+
+                // pixels whose centers fall within the span
+                let start = (pair[0].0 - 0.5).ceil().max(0.0) as u32;
+                let end = ((pair[1].0 - 0.5).ceil().max(0.0) as u32).min(width);
+                for px in start..end {
+                    self.canvas.put_pixel(px, py, Luma([luma]));
+                    self.inked |= luma < 128;
+                }
+            }
+        }
+    }
+}
+
+/// Rasterizes the filled paths drawn by a page, e.g. QR codes drawn module by module;
+/// `None` if nothing dark is drawn
+pub(super) fn rasterize(
+    content: &Content,
+    media_box: Rect,
+    xobjects: &Dictionary,
+    resolve: &impl Resolve,
+) -> Result<Option<GrayImage>, PdfError> {
+    let page_width = media_box.right - media_box.left;
+    let page_height = media_box.top - media_box.bottom;
+    if page_width <= 0.0 || page_height <= 0.0 {
+        return Ok(None);
+    }
+
+    let scale = MAX_DIMENSION / page_width.max(page_height);
+    let width = (page_width * scale).round().max(1.0) as u32;
+    let height = (page_height * scale).round().max(1.0) as u32;
+
+    // user space has the origin at the bottom left, images at the top left
+    let device = Matrix([
+        scale,
+        0.0,
+        0.0,
+        -scale,
+        -media_box.left * scale,
+        media_box.top * scale,
+    ]);
+
+    let mut rasterizer = Rasterizer {
+        canvas: GrayImage::from_pixel(width, height, Luma([u8::MAX])),
+        resolve,
+        inked: false,
+    };
+
+    let state = State {
+        ctm: device,
+        fill: 0,
+    };
+    rasterizer.run(&content.operations, state, xobjects, 0)?;
+
+    if rasterizer.inked {
+        Ok(Some(rasterizer.canvas))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ErrorCorrection, QrDecoder, QrRenderer};
+    use assert2::{check, let_assert};
+    use image::DynamicImage;
+    use pdf::object::NoResolve;
+    use pdf::primitive::PdfStream;
+
+    mod data {
+        use super::*;
+
+        pub fn payload() -> &'static str {
+            include_str!("../../testdata/1.payload")
+        }
+
+        /// One `re` per dark module, one unit per module
+        pub fn modules() -> (u32, String) {
+            let image = QrRenderer::new(payload(), ErrorCorrection::Medium)
+                .expect("QR code rendering failed")
+                .module_size(1)
+                .to_image()
+                .to_luma8();
+
+            let size = image.height();
+            let rects = image
+                .enumerate_pixels()
+                .filter(|(_, _, p)| p[0] < 128)
+                .map(|(x, y, _)| format!("{} {} 1 1 re\n", x, size - 1 - y))
+                .collect();
+
+            (size, rects)
+        }
+
+        pub fn stream(info: Vec<(&str, Primitive)>, content: &str) -> PdfStream {
+            let mut dict = Dictionary::new();
+            dict.insert("Length", Primitive::Integer(content.len() as i32));
+            for (key, value) in info {
+                dict.insert(key, value);
+            }
+
+            PdfStream {
+                info: dict,
+                data: content.as_bytes().to_vec(),
+            }
+        }
+
+        pub fn content(content: &str) -> Content {
+            // the last operator is only read when followed by whitespace
+            let content = format!("{}\n", content);
+
+            Content::from_primitive(Primitive::Stream(stream(Vec::new(), &content)), &NoResolve)
+                .expect("invalid content stream")
+        }
+
+        pub fn page(size: f32) -> Rect {
+            Rect {
+                left: 0.0,
+                bottom: 0.0,
+                right: size,
+                top: size,
+            }
+        }
+    }
+
+    #[test]
+    fn vector_qr() {
+        let (size, modules) = data::modules();
+        // white background, then the modules scaled up and offset in a saved state
+        let content = data::content(&format!(
+            "1 g 0 0 {page} {page} re f q 0 0 0 rg 2 0 0 2 10 10 cm\n{modules}f Q",
+            page = size * 2 + 20,
+            modules = modules
+        ));
+
+        let_assert!(
+            Ok(Some(image)) = rasterize(
+                &content,
+                data::page((size * 2 + 20) as f32),
+                &Dictionary::new(),
+                &NoResolve
+            )
+        );
+        check!(image.width() == MAX_DIMENSION as u32);
+
+        let_assert!(
+            Ok(payload) = QrDecoder::new().image_get_payload(DynamicImage::ImageLuma8(image))
+        );
+        check!(payload == data::payload());
+    }
+
+    #[test]
+    fn form_xobject() {
+        let (size, modules) = data::modules();
+        let form = data::stream(
+            vec![
+                ("Subtype", Primitive::name("Form")),
+                (
+                    "Matrix",
+                    Primitive::Array(
+                        [2, 0, 0, 2, 10, 10]
+                            .iter()
+                            .map(|&n| Primitive::Integer(n))
+                            .collect(),
+                    ),
+                ),
+            ],
+            &format!("{}f\n", modules),
+        );
+        let mut xobjects = Dictionary::new();
+        xobjects.insert("Fm0", Primitive::Stream(form));
+
+        let content = data::content("/Fm0 Do");
+
+        let_assert!(
+            Ok(Some(image)) = rasterize(
+                &content,
+                data::page((size * 2 + 20) as f32),
+                &xobjects,
+                &NoResolve
+            )
+        );
+        let_assert!(
+            Ok(payload) = QrDecoder::new().image_get_payload(DynamicImage::ImageLuma8(image))
+        );
+        check!(payload == data::payload());
+    }
+
+    #[test]
+    fn fill_rules() {
+        // a square with a square hole, sampled at the center of the hole
+        let path = "0 0 10 10 re 2 2 6 6 re";
+        let center = (MAX_DIMENSION / 2.0) as u32;
+
+        let_assert!(
+            Ok(Some(image)) = rasterize(
+                &data::content(&format!("{} f", path)),
+                data::page(10.0),
+                &Dictionary::new(),
+                &NoResolve
+            )
+        );
+        check!(image.get_pixel(center, center)[0] == 0);
+
+        let_assert!(
+            Ok(Some(image)) = rasterize(
+                &data::content(&format!("{} f*", path)),
+                data::page(10.0),
+                &Dictionary::new(),
+                &NoResolve
+            )
+        );
+        check!(image.get_pixel(center, center)[0] == u8::MAX);
+        check!(image.get_pixel(10, 10)[0] == 0);
+    }
+
+    #[test]
+    fn nothing_drawn() {
+        // strokes and light fills don't count
+        let content =
+            data::content("0 g 0 0 10 10 re S 0.9 g 0 0 5 5 re f BT /F1 12 Tf (text) Tj ET");
+
+        let_assert!(
+            Ok(image) = rasterize(&content, data::page(10.0), &Dictionary::new(), &NoResolve)
+        );
+        check!(image.is_none());
+    }
+}
@@ -0,0 +1,292 @@
+use image::{DynamicImage, GrayImage};
+use std::io::{self, Write};
+
+/// How the QR image is stored in the generated PDF
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum PdfImageEncoding {
+    /// 1 bit per pixel grayscale, as in the official certificates
+    #[default]
+    Luma1,
+    /// 8 bits per pixel grayscale
+    Luma8,
+}
+
+/// Generates single page PDFs laid out like the official `potwierdzenieQrSzczepienia.pdf`
+#[derive(Debug, Clone, Default)]
+pub struct PdfQrGenerator {
+    encoding: PdfImageEncoding,
+    title: Option<String>,
+}
+
+impl PdfQrGenerator {
+    // dimensions of the official document, in points
+    const PAGE_SIZE: &'static str = "53.86";
+    const IMAGE_SIZE: &'static str = "52.56";
+
+    const LUMA1_THRESHOLD: u8 = 128;
+
+    pub fn new(encoding: PdfImageEncoding) -> Self {
+        PdfQrGenerator {
+            encoding,
+            title: None,
+        }
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn generate(&self, image: &DynamicImage) -> Vec<u8> {
+        let mut buf = Vec::new();
+
+        self.write(image, &mut buf)
+            .expect("writing to a Vec can't fail");
+
+        buf
+    }
+
+    pub fn write(&self, image: &DynamicImage, writer: impl Write) -> io::Result<()> {
+        self.write_pages(std::slice::from_ref(image), writer)
+    }
+
+    /// Generates a PDF with one page per image, e.g. for a whole family
+    pub fn generate_pages(&self, images: &[DynamicImage]) -> Vec<u8> {
+        let mut buf = Vec::new();
+
+        self.write_pages(images, &mut buf)
+            .expect("writing to a Vec can't fail");
+
+        buf
+    }
+
+    pub fn write_pages(&self, images: &[DynamicImage], mut writer: impl Write) -> io::Result<()> {
+        // catalog and page tree come first, then 3 objects per page
+        const FIRST_PAGE: usize = 3;
+        const OBJECTS_PER_PAGE: usize = 3;
+
+        let content = format!(
+            "q\n{size} 0 0 {size} 0 0 cm\n/X0 Do\nQ\n",
+            size = Self::IMAGE_SIZE
+        );
+
+        let kids = (0..images.len())
+            .map(|i| format!("{} 0 R", FIRST_PAGE + i * OBJECTS_PER_PAGE))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut pdf = PdfBuilder::default();
+
+        pdf.object(b"<</Type/Catalog/Pages 2 0 R>>");
+        pdf.object(format!("<</Type/Pages/Count {}/Kids[{}]>>", images.len(), kids).as_bytes());
+
+        for image in images {
+            let image = image.to_luma8();
+
+            let (bits_per_component, data) = match self.encoding {
+                PdfImageEncoding::Luma1 => (1, Self::convert_luma8_to_luma1(&image)),
+                PdfImageEncoding::Luma8 => (8, image.as_raw().clone()),
+            };
+            let data = deflate::deflate_bytes_zlib(&data);
+
+            // the image and content streams follow the page
+            pdf.object(
+                format!(
+                    "<</Type/Page/Parent 2 0 R/MediaBox[0 0 {size} {size}]\
+                     /Resources<</XObject<</X0 {image} 0 R>>>>/Contents {content} 0 R>>",
+                    size = Self::PAGE_SIZE,
+                    image = pdf.next_id() + 1,
+                    content = pdf.next_id() + 2,
+                )
+                .as_bytes(),
+            );
+            pdf.stream(
+                &format!(
+                    "<</Type/XObject/Subtype/Image/Width {}/Height {}/Interpolate false\
+                     /BitsPerComponent {}/ColorSpace/DeviceGray/Filter/FlateDecode/Length {}>>",
+                    image.width(),
+                    image.height(),
+                    bits_per_component,
+                    data.len()
+                ),
+                &data,
+            );
+            pdf.stream(
+                &format!("<</Length {}>>", content.len()),
+                content.as_bytes(),
+            );
+        }
+
+        let info = self
+            .title
+            .as_ref()
+            .map(|title| pdf.object(format!("<</Title({})>>", escape_string(title)).as_bytes()));
+
+        writer.write_all(&pdf.finish(info))
+    }
+
+    fn convert_luma8_to_luma1(image: &GrayImage) -> Vec<u8> {
+        let width = image.width() as usize;
+
+        // every row is padded to a full byte
+        image
+            .as_raw()
+            .chunks(width)
+            .flat_map(|row| {
+                row.chunks(8).map(|pixels| {
+                    pixels
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, &pixel)| pixel >= Self::LUMA1_THRESHOLD)
+                        .fold(0u8, |byte, (pos, _)| byte | (0x80 >> pos))
+                })
+            })
+            .collect()
+    }
+}
+
+fn escape_string(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_ascii() && !c.is_ascii_control())
+        .flat_map(|c| match c {
+            '(' | ')' | '\\' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect()
+}
+
+// minimal PDF file writer; object numbers are assigned sequentially from 1
+#[derive(Default)]
+pub(crate) struct PdfBuilder {
+    buf: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl PdfBuilder {
+    const HEADER: &'static [u8] = b"%PDF-1.3\n";
+
+    pub(crate) fn next_id(&self) -> usize {
+        self.offsets.len() + 1
+    }
+
+    fn begin_object(&mut self) -> usize {
+        if self.buf.is_empty() {
+            self.buf.extend_from_slice(Self::HEADER);
+        }
+
+        self.offsets.push(self.buf.len());
+
+        let id = self.offsets.len();
+        self.buf
+            .extend_from_slice(format!("{} 0 obj", id).as_bytes());
+
+        id
+    }
+
+    pub(crate) fn object(&mut self, dict: &[u8]) -> usize {
+        let id = self.begin_object();
+
+        self.buf.extend_from_slice(dict);
+        self.buf.extend_from_slice(b"endobj\n");
+
+        id
+    }
+
+    pub(crate) fn stream(&mut self, dict: &str, data: &[u8]) -> usize {
+        let id = self.begin_object();
+
+        self.buf.extend_from_slice(dict.as_bytes());
+        self.buf.extend_from_slice(b"stream\n");
+        self.buf.extend_from_slice(data);
+        self.buf.extend_from_slice(b"\nendstream endobj\n");
+
+        id
+    }
+
+    pub(crate) fn finish(mut self, info: Option<usize>) -> Vec<u8> {
+        let xref = self.buf.len();
+        let size = self.offsets.len() + 1;
+
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", size);
+        for offset in &self.offsets {
+            trailer.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+
+        trailer.push_str(&format!("trailer\n<</Root 1 0 R/Size {}", size));
+        if let Some(info) = info {
+            trailer.push_str(&format!("/Info {} 0 R", info));
+        }
+        trailer.push_str(&format!(">>\nstartxref\n{}\n%%EOF\n", xref));
+
+        self.buf.extend_from_slice(trailer.as_bytes());
+
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ErrorCorrection, PdfQrExtractor, QrDecoder, QrRenderer};
+    use assert2::{check, let_assert};
+
+    mod data {
+        use super::*;
+
+        pub fn case1() -> &'static str {
+            include_str!("../testdata/1.payload")
+        }
+
+        pub fn image(payload: &str) -> DynamicImage {
+            QrRenderer::new(payload, ErrorCorrection::Medium)
+                .expect("QR code rendering failed")
+                .module_size(3)
+                .to_image()
+        }
+    }
+
+    fn round_trip(encoding: PdfImageEncoding) {
+        let payload = data::case1();
+        let image = data::image(payload);
+
+        let pdf = PdfQrGenerator::new(encoding)
+            .title("Test QR")
+            .generate(&image);
+
+        let_assert!(Ok(extractor) = PdfQrExtractor::from_vec(pdf));
+        let_assert!(Some(extracted) = extractor.images().next());
+        let_assert!(Ok(extracted) = extracted);
+
+        check!(extracted.to_luma8() == image.to_luma8());
+
+        let mut qr = QrDecoder::new();
+
+        let_assert!(Ok(decoded) = qr.image_get_payload(extracted));
+        check!(decoded == payload);
+    }
+
+    #[test]
+    fn luma1() {
+        round_trip(PdfImageEncoding::Luma1);
+    }
+
+    #[test]
+    fn luma8() {
+        round_trip(PdfImageEncoding::Luma8);
+    }
+
+    #[test]
+    fn luma1_packing() {
+        let image = GrayImage::from_raw(
+            10,
+            2,
+            vec![
+                255, 0, 255, 0, 255, 0, 255, 0, 255, 255, //
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 255,
+            ],
+        )
+        .unwrap();
+
+        check!(PdfQrGenerator::convert_luma8_to_luma1(&image) == [0xaa, 0xc0, 0x00, 0x40]);
+    }
+}
@@ -0,0 +1,277 @@
+use crate::vaccination_info::VaccinationInfo;
+use chrono::{Duration, NaiveDate};
+
+/// Validity rule beyond the printed expiration date
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "rule", rename_all = "snake_case"))]
+pub enum Rule {
+    /// Valid only from this many days after the issue date
+    MinDaysAfterIssue { days: u32 },
+    /// Valid for at most this many days after the issue date
+    MaxAge { days: u32 },
+    /// Only these vaccine types are accepted
+    VaccineTypes { allowed: Vec<String> },
+    /// From `from` on, certificates issued before `issued_before` are rejected
+    Cutoff {
+        from: NaiveDate,
+        issued_before: NaiveDate,
+    },
+}
+
+/// Outcome of a single rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RuleResult {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub rule: Rule,
+    pub passed: bool,
+    /// Why the rule passed or failed, in plain words
+    pub reason: String,
+}
+
+/// Outcome of every rule of a [`Policy`], in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct PolicyResult {
+    pub rules: Vec<RuleResult>,
+}
+
+impl PolicyResult {
+    pub fn passed(&self) -> bool {
+        self.rules.iter().all(|rule| rule.passed)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &RuleResult> + '_ {
+        self.rules.iter().filter(|rule| !rule.passed)
+    }
+}
+
+/// Set of rules a certificate has to pass, e.g. a venue's entry requirements
+///
+/// With the `serde` feature it can be loaded from any format serde supports, e.g. TOML:
+///
+/// ```toml
+/// [[rules]]
+/// rule = "min_days_after_issue"
+/// days = 14
+///
+/// [[rules]]
+/// rule = "cutoff"
+/// from = "2022-02-01"
+/// issued_before = "2021-08-01"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Policy {
+    rules: Vec<Rule>,
+}
+
+impl Policy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Checks every rule as of `date`
+    pub fn check(&self, info: &VaccinationInfo, date: NaiveDate) -> PolicyResult {
+        PolicyResult {
+            rules: self
+                .rules
+                .iter()
+                .map(|rule| rule.check(info, date))
+                .collect(),
+        }
+    }
+}
+
+impl Rule {
+    pub fn check(&self, info: &VaccinationInfo, date: NaiveDate) -> RuleResult {
+        let result = |passed, reason| RuleResult {
+            rule: self.clone(),
+            passed,
+            reason,
+        };
+        let days_after_issue = |days: u32| {
+            info.issue_date()
+                .checked_add_signed(Duration::days(days.into()))
+        };
+
+        match self {
+            Rule::MinDaysAfterIssue { days } => match days_after_issue(*days) {
+                Some(from) if from <= date => result(
+                    true,
+                    format!("valid since {}, {} days after issue", from, days),
+                ),
+                Some(from) => result(
+                    false,
+                    format!("not valid until {}, {} days after issue", from, days),
+                ),
+                None => result(false, format!("never valid, {} days after issue", days)),
+            },
+            Rule::MaxAge { days } => match days_after_issue(*days) {
+                Some(until) if date > until => result(
+                    false,
+                    format!("no longer valid after {}, {} days after issue", until, days),
+                ),
+                Some(until) => result(
+                    true,
+                    format!("valid until {}, {} days after issue", until, days),
+                ),
+                None => result(true, format!("valid for {} days after issue", days)),
+            },
+            Rule::VaccineTypes { allowed } => {
+                let vaccine_type = info.vaccine_type();
+
+                if allowed.iter().any(|allowed| allowed == vaccine_type) {
+                    result(true, format!("vaccine type {} is allowed", vaccine_type))
+                } else {
+                    result(
+                        false,
+                        format!("vaccine type {} is not allowed", vaccine_type),
+                    )
+                }
+            }
+            Rule::Cutoff {
+                from,
+                issued_before,
+            } => {
+                if date < *from {
+                    result(true, format!("cutoff not in effect until {}", from))
+                } else if info.issue_date() < *issued_before {
+                    result(
+                        false,
+                        format!(
+                            "issued before {}, not accepted since {}",
+                            issued_before, from
+                        ),
+                    )
+                } else {
+                    result(true, format!("issued on or after {}", issued_before))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{check, let_assert};
+    use std::str::FromStr;
+
+    mod data {
+        use super::*;
+
+        /// Issued on 2021-01-20, expires on 2022-01-20, vaccine type 321
+        pub fn info() -> VaccinationInfo {
+            VaccinationInfo::from_str(include_str!("../testdata/1.plain"))
+                .expect("Malformed test record")
+        }
+
+        pub fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+            NaiveDate::from_ymd(y, m, d)
+        }
+    }
+
+    fn passes(rule: Rule, date: NaiveDate) -> bool {
+        rule.check(&data::info(), date).passed
+    }
+
+    #[test]
+    fn min_days_after_issue() {
+        let rule = || Rule::MinDaysAfterIssue { days: 14 };
+
+        check!(!passes(rule(), data::date(2021, 2, 2)));
+        check!(passes(rule(), data::date(2021, 2, 3)));
+    }
+
+    #[test]
+    fn max_age() {
+        let rule = || Rule::MaxAge { days: 10 };
+
+        check!(passes(rule(), data::date(2021, 1, 30)));
+        check!(!passes(rule(), data::date(2021, 1, 31)));
+    }
+
+    #[test]
+    fn vaccine_types() {
+        let allowed = |types: &[&str]| Rule::VaccineTypes {
+            allowed: types.iter().map(|&t| t.to_owned()).collect(),
+        };
+
+        check!(passes(allowed(&["123", "321"]), data::date(2021, 2, 1)));
+        check!(!passes(allowed(&["123"]), data::date(2021, 2, 1)));
+    }
+
+    #[test]
+    fn cutoff() {
+        let rule = |issued_before| Rule::Cutoff {
+            from: data::date(2021, 6, 1),
+            issued_before,
+        };
+
+        check!(passes(
+            rule(data::date(2021, 3, 1)),
+            data::date(2021, 5, 31)
+        ));
+        check!(!passes(
+            rule(data::date(2021, 3, 1)),
+            data::date(2021, 6, 1)
+        ));
+        check!(passes(
+            rule(data::date(2021, 1, 20)),
+            data::date(2021, 6, 1)
+        ));
+    }
+
+    #[test]
+    fn policy() {
+        let policy = Policy::new()
+            .rule(Rule::MinDaysAfterIssue { days: 14 })
+            .rule(Rule::MaxAge { days: 180 });
+
+        check!(policy.check(&data::info(), data::date(2021, 3, 1)).passed());
+
+        let result = policy.check(&data::info(), data::date(2021, 12, 1));
+        check!(!result.passed());
+        let failures: Vec<_> = result.failures().collect();
+        let_assert!([failure] = &failures[..]);
+        check!(failure.rule == Rule::MaxAge { days: 180 });
+        check!(failure.reason == "no longer valid after 2021-07-19, 180 days after issue");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize() {
+        let json = r#"{"rules": [
+            {"rule": "min_days_after_issue", "days": 14},
+            {"rule": "vaccine_types", "allowed": ["321"]},
+            {"rule": "cutoff", "from": "2022-02-01", "issued_before": "2021-08-01"}
+        ]}"#;
+
+        let_assert!(Ok(policy) = serde_json::from_str::<Policy>(json));
+        check!(
+            policy.rules()
+                == [
+                    Rule::MinDaysAfterIssue { days: 14 },
+                    Rule::VaccineTypes {
+                        allowed: vec!["321".to_owned()]
+                    },
+                    Rule::Cutoff {
+                        from: data::date(2022, 2, 1),
+                        issued_before: data::date(2021, 8, 1),
+                    },
+                ]
+        );
+    }
+}
@@ -0,0 +1,429 @@
+use crate::qr::Point;
+use image::{imageops, GrayImage, Luma};
+use std::fmt;
+
+/// Single image transformation applied before looking for QR codes again
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Preprocess {
+    /// Shrinks images larger than `max_dimension` pixels on either side
+    Downscale {
+        max_dimension: u32,
+    },
+    /// Stretches the histogram to the full range, ignoring the darkest and brightest 1%
+    ContrastStretch,
+    /// Binarizes against the mean of the surrounding `(2 * radius + 1)²` block,
+    /// which copes with uneven lighting
+    AdaptiveThreshold {
+        radius: u32,
+    },
+    /// Rotates clockwise around the center, keeping the image size
+    Rotate {
+        degrees: f32,
+    },
+    /// Swaps black and white, for codes printed light on dark
+    Invert,
+    Sharpen,
+}
+
+impl Preprocess {
+    const SHARPEN_KERNEL: [f32; 9] = [0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0];
+    // pixels darker than the block mean by at least this much become black
+    const THRESHOLD_OFFSET: i64 = 7;
+    const BACKGROUND: Luma<u8> = Luma([255]);
+
+    pub fn apply(self, image: &GrayImage) -> GrayImage {
+        match self {
+            Preprocess::Downscale { max_dimension } => downscale(image, max_dimension),
+            Preprocess::ContrastStretch => contrast_stretch(image),
+            Preprocess::AdaptiveThreshold { radius } => adaptive_threshold(image, radius),
+            Preprocess::Rotate { degrees } => rotate(image, degrees),
+            Preprocess::Invert => {
+                let mut image = image.clone();
+                imageops::invert(&mut image);
+                image
+            }
+            Preprocess::Sharpen => imageops::filter3x3(image, &Self::SHARPEN_KERNEL),
+        }
+    }
+
+    /// Maps a point in the output of [`apply`](Self::apply) back to the input image
+    pub fn map_back(self, point: Point, input: (u32, u32), output: (u32, u32)) -> Point {
+        match self {
+            Preprocess::Downscale { .. } => Point {
+                x: (point.x as f64 * input.0 as f64 / output.0 as f64).round() as i32,
+                y: (point.y as f64 * input.1 as f64 / output.1 as f64).round() as i32,
+            },
+            Preprocess::Rotate { degrees } => {
+                let (x, y) = unrotate(
+                    (point.x as f64, point.y as f64),
+                    degrees,
+                    center(input.0, input.1),
+                );
+
+                Point {
+                    x: x.round() as i32,
+                    y: y.round() as i32,
+                }
+            }
+            _ => point,
+        }
+    }
+}
+
+impl fmt::Display for Preprocess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Preprocess::Downscale { max_dimension } => {
+                write!(f, "downscale to at most {}px", max_dimension)
+            }
+            Preprocess::ContrastStretch => write!(f, "contrast stretch"),
+            Preprocess::AdaptiveThreshold { radius } => {
+                write!(f, "adaptive threshold (radius {})", radius)
+            }
+            Preprocess::Rotate { degrees } => write!(f, "rotate {}°", degrees),
+            Preprocess::Invert => write!(f, "invert"),
+            Preprocess::Sharpen => write!(f, "sharpen"),
+        }
+    }
+}
+
+fn downscale(image: &GrayImage, max_dimension: u32) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let longest = width.max(height);
+
+    if longest <= max_dimension {
+        return image.clone();
+    }
+
+    let scale = max_dimension as f64 / longest as f64;
+    let width = ((width as f64 * scale).round() as u32).max(1);
+    let height = ((height as f64 * scale).round() as u32).max(1);
+
+    imageops::resize(image, width, height, imageops::FilterType::Triangle)
+}
+
+fn contrast_stretch(image: &GrayImage) -> GrayImage {
+    let mut histogram = [0usize; 256];
+    for Luma([p]) in image.pixels() {
+        histogram[*p as usize] += 1;
+    }
+
+    let clip = image.pixels().len() / 100;
+
+    // first level, coming from either end, past the clipped pixels
+    let clipped = |levels: &mut dyn Iterator<Item = usize>| {
+        let mut seen = 0;
+        for level in levels {
+            seen += histogram[level];
+            if seen > clip {
+                return level;
+            }
+        }
+        0
+    };
+
+    let low = clipped(&mut (0..256));
+    let high = clipped(&mut (0..256).rev());
+
+    if high <= low {
+        return image.clone();
+    }
+
+    let mut image = image.clone();
+    for Luma([p]) in image.pixels_mut() {
+        let stretched = (*p as isize - low as isize) * 255 / (high - low) as isize;
+        *p = stretched.clamp(0, 255) as u8;
+    }
+
+    image
+}
+
+fn adaptive_threshold(image: &GrayImage, radius: u32) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let (w, h) = (width as usize, height as usize);
+
+    // summed area table with an extra zero row and column
+    let mut integral = vec![0i64; (w + 1) * (h + 1)];
+    for y in 0..h {
+        let mut row = 0;
+        for x in 0..w {
+            row += image.get_pixel(x as u32, y as u32)[0] as i64;
+            integral[(y + 1) * (w + 1) + x + 1] = integral[y * (w + 1) + x + 1] + row;
+        }
+    }
+
+    let r = radius as usize;
+
+    GrayImage::from_fn(width, height, |x, y| {
+        let (x, y) = (x as usize, y as usize);
+        let (x0, y0) = (x.saturating_sub(r), y.saturating_sub(r));
+        let (x1, y1) = ((x + r + 1).min(w), (y + r + 1).min(h));
+
+        let sum =
+            integral[y1 * (w + 1) + x1] - integral[y0 * (w + 1) + x1] - integral[y1 * (w + 1) + x0]
+                + integral[y0 * (w + 1) + x0];
+        let count = ((x1 - x0) * (y1 - y0)) as i64;
+
+        let value = image.get_pixel(x as u32, y as u32)[0] as i64;
+
+        if value * count < sum - Preprocess::THRESHOLD_OFFSET * count {
+            Luma([0])
+        } else {
+            Luma([255])
+        }
+    })
+}
+
+fn center(width: u32, height: u32) -> (f64, f64) {
+    (width as f64 / 2.0, height as f64 / 2.0)
+}
+
+/// Position in the source image of a point of the image rotated by `degrees`
+fn unrotate((x, y): (f64, f64), degrees: f32, (cx, cy): (f64, f64)) -> (f64, f64) {
+    let (sin, cos) = (-(degrees as f64)).to_radians().sin_cos();
+    let (dx, dy) = (x - cx, y - cy);
+
+    (cx + dx * cos - dy * sin, cy + dx * sin + dy * cos)
+}
+
+fn rotate(image: &GrayImage, degrees: f32) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let center = center(width, height);
+
+    GrayImage::from_fn(width, height, |x, y| {
+        // sample at the pixel center
+        let (sx, sy) = unrotate((x as f64 + 0.5, y as f64 + 0.5), degrees, center);
+        let (sx, sy) = (sx.floor(), sy.floor());
+
+        if sx >= 0.0 && sy >= 0.0 && sx < width as f64 && sy < height as f64 {
+            *image.get_pixel(sx as u32, sy as u32)
+        } else {
+            Preprocess::BACKGROUND
+        }
+    })
+}
+
+/// Sequences of [`Preprocess`] steps, tried in order until one of them yields a readable code
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preprocessing {
+    chains: Vec<Vec<Preprocess>>,
+}
+
+impl Preprocessing {
+    const MAX_DIMENSION: u32 = 1600;
+    const THRESHOLD_RADIUS: u32 = 15;
+    const ROTATIONS: [f32; 6] = [10.0, -10.0, 20.0, -20.0, 30.0, -30.0];
+
+    /// No preprocessing; add chains with [`chain`](Self::chain)
+    pub fn none() -> Self {
+        Preprocessing { chains: Vec::new() }
+    }
+
+    pub fn chain(mut self, steps: impl Into<Vec<Preprocess>>) -> Self {
+        self.chains.push(steps.into());
+        self
+    }
+
+    pub fn chains(&self) -> &[Vec<Preprocess>] {
+        &self.chains
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+    }
+}
+
+/// Downscaled, then contrast, thresholding, sharpening, inversion and finally rotations
+impl Default for Preprocessing {
+    fn default() -> Self {
+        let downscale = Preprocess::Downscale {
+            max_dimension: Self::MAX_DIMENSION,
+        };
+        let threshold = Preprocess::AdaptiveThreshold {
+            radius: Self::THRESHOLD_RADIUS,
+        };
+
+        let mut preprocessing = Preprocessing::none()
+            .chain([downscale])
+            .chain([downscale, Preprocess::ContrastStretch])
+            .chain([downscale, threshold])
+            .chain([downscale, Preprocess::Sharpen])
+            .chain([downscale, Preprocess::Sharpen, threshold])
+            .chain([downscale, Preprocess::Invert])
+            .chain([downscale, Preprocess::ContrastStretch, Preprocess::Invert]);
+
+        for &degrees in &Self::ROTATIONS {
+            preprocessing =
+                preprocessing.chain([downscale, Preprocess::Rotate { degrees }, threshold]);
+        }
+
+        preprocessing
+    }
+}
+
+/// Image dimensions before and after a step
+pub(crate) type Resize = ((u32, u32), (u32, u32));
+
+/// Applies the steps in order, returning the image along with each step's input and output sizes
+pub(crate) fn apply_chain(image: &GrayImage, steps: &[Preprocess]) -> (GrayImage, Vec<Resize>) {
+    let mut image = image.clone();
+    let mut sizes = Vec::with_capacity(steps.len());
+
+    for step in steps {
+        let input = image.dimensions();
+        image = step.apply(&image);
+        sizes.push((input, image.dimensions()));
+    }
+
+    (image, sizes)
+}
+
+/// Maps a point found after [`apply_chain`] back to the original image
+pub(crate) fn map_back_chain(point: Point, steps: &[Preprocess], sizes: &[Resize]) -> Point {
+    steps
+        .iter()
+        .zip(sizes)
+        .rev()
+        .fold(point, |point, (step, &(input, output))| {
+            step.map_back(point, input, output)
+        })
+}
+
+/// Record of a single detection pass
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attempt {
+    /// Name of the [`QrDetector`](crate::QrDetector) used
+    pub backend: String,
+    /// Preprocessing applied, empty for the image as is
+    pub steps: Vec<Preprocess>,
+    /// QR codes located in the image
+    pub located: usize,
+    /// Located codes whose payload could be read
+    pub decoded: usize,
+    /// Why the detector failed, if it did
+    pub error: Option<String>,
+}
+
+impl Attempt {
+    pub fn succeeded(&self) -> bool {
+        self.decoded > 0
+    }
+}
+
+impl fmt::Display for Attempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} on ", self.backend)?;
+
+        if self.steps.is_empty() {
+            write!(f, "original")?;
+        }
+
+        for (i, step) in self.steps.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", step)?;
+        }
+
+        match &self.error {
+            Some(error) => write!(f, ": failed, {}", error),
+            None => write!(f, ": {} located, {} decoded", self.located, self.decoded),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::check;
+
+    mod data {
+        use super::*;
+
+        pub fn gradient() -> GrayImage {
+            GrayImage::from_fn(64, 32, |x, _| Luma([100 + (x / 2) as u8]))
+        }
+    }
+
+    #[test]
+    fn contrast_stretch() {
+        let image = Preprocess::ContrastStretch.apply(&data::gradient());
+
+        let min = image.pixels().map(|p| p[0]).min();
+        let max = image.pixels().map(|p| p[0]).max();
+
+        check!(min == Some(0));
+        check!(max == Some(255));
+    }
+
+    #[test]
+    fn adaptive_threshold() {
+        // dark square on an unevenly lit background
+        let image = GrayImage::from_fn(64, 64, |x, y| {
+            let background = 120 + x as u8 * 2;
+            if (24..40).contains(&x) && (24..40).contains(&y) {
+                Luma([background - 60])
+            } else {
+                Luma([background])
+            }
+        });
+
+        let image = Preprocess::AdaptiveThreshold { radius: 8 }.apply(&image);
+
+        check!(image.get_pixel(30, 30)[0] == 0);
+        check!(image.get_pixel(5, 5)[0] == 255);
+        check!(image.get_pixel(60, 60)[0] == 255);
+    }
+
+    #[test]
+    fn invert() {
+        let image = Preprocess::Invert.apply(&data::gradient());
+
+        check!(image.get_pixel(0, 0)[0] == 155);
+    }
+
+    #[test]
+    fn downscale() {
+        let step = Preprocess::Downscale { max_dimension: 16 };
+
+        let image = step.apply(&data::gradient());
+        check!(image.dimensions() == (16, 8));
+
+        let point = step.map_back(Point { x: 4, y: 2 }, (64, 32), image.dimensions());
+        check!(point == Point { x: 16, y: 8 });
+
+        // smaller images are left alone
+        let step = Preprocess::Downscale { max_dimension: 100 };
+        check!(step.apply(&data::gradient()).dimensions() == (64, 32));
+    }
+
+    #[test]
+    fn rotate() {
+        let mut image = GrayImage::from_pixel(41, 41, Luma([255]));
+        image.put_pixel(30, 20, Luma([0]));
+
+        let step = Preprocess::Rotate { degrees: 90.0 };
+        let rotated = step.apply(&image);
+
+        // clockwise: right of the center ends up below it
+        check!(rotated.get_pixel(20, 30)[0] == 0);
+
+        let point = step.map_back(Point { x: 20, y: 30 }, (41, 41), (41, 41));
+        check!((point.x - 30).abs() <= 1);
+        check!((point.y - 20).abs() <= 1);
+    }
+
+    #[test]
+    fn chain() {
+        let steps = [
+            Preprocess::Downscale { max_dimension: 32 },
+            Preprocess::Rotate { degrees: 180.0 },
+        ];
+
+        let (image, sizes) = apply_chain(&data::gradient(), &steps);
+        check!(image.dimensions() == (32, 16));
+
+        let point = map_back_chain(Point { x: 24, y: 12 }, &steps, &sizes);
+        check!(point == Point { x: 16, y: 8 });
+    }
+}
@@ -1,15 +1,126 @@
 use crate::error::QrError;
-use image::DynamicImage;
+use crate::preprocess::{self, Attempt, Preprocess, Preprocessing, Resize};
+use crate::qr_backend::{QrBackend, QrDetector};
+use image::{DynamicImage, GrayImage};
+use std::fmt;
+use std::io::Read;
 use std::path::Path;
+use std::sync::Arc;
 
-#[derive(Debug, Clone, Default)]
+// the only supported payload version
+pub(crate) static PAYLOAD_VERSION: &str = "1";
+
+/// Pixel position in the scanned image
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// QR code located in an image; the payload is decoded independently for every code
+#[derive(Debug)]
+pub struct FoundCode {
+    /// Corners of the code, clockwise from the top left one
+    pub corners: [Point; 4],
+    pub payload: Result<String, QrError>,
+    /// Preprocessing applied before the code could be read, empty if none was needed
+    pub preprocessing: Vec<Preprocess>,
+    /// Name of the [`QrDetector`] that found the code
+    pub backend: String,
+}
+
+impl FoundCode {
+    pub fn into_encrypted(self) -> Result<Vec<u8>, QrError> {
+        QrDecoder::decode_payload(&self.payload?)
+    }
+}
+
+/// Codes found in an image, along with every detection pass that was tried
+#[derive(Debug)]
+pub struct Scan {
+    pub codes: Vec<FoundCode>,
+    pub attempts: Vec<Attempt>,
+}
+
+/// Reads QR codes from images with one or more [`QrDetector`]s
+///
+/// Every clone gets detectors of its own, so clones can scan in parallel.
+#[derive(Clone)]
 pub struct QrDecoder {
-    decoder: quircs::Quirc,
+    detectors: Vec<Detector>,
+    preprocessing: Option<Preprocessing>,
+}
+
+type DetectorFactory = Arc<dyn Fn() -> Box<dyn QrDetector> + Send + Sync>;
+
+/// Detector along with the factory that made it, to make another one for clones
+struct Detector {
+    factory: DetectorFactory,
+    detector: Box<dyn QrDetector>,
+}
+
+impl Detector {
+    fn new(factory: impl Fn() -> Box<dyn QrDetector> + Send + Sync + 'static) -> Self {
+        Detector {
+            detector: factory(),
+            factory: Arc::new(factory),
+        }
+    }
+}
+
+impl Clone for Detector {
+    fn clone(&self) -> Self {
+        Detector {
+            factory: self.factory.clone(),
+            detector: (self.factory)(),
+        }
+    }
 }
 
 impl QrDecoder {
     pub fn new() -> Self {
-        Self::default()
+        Self::with_backend(QrBackend::default())
+    }
+
+    pub fn with_backend(backend: QrBackend) -> Self {
+        Self::with_detector(move || backend.detector())
+    }
+
+    /// Decoder using the detectors made by `factory`, one for each clone
+    pub fn with_detector(
+        factory: impl Fn() -> Box<dyn QrDetector> + Send + Sync + 'static,
+    ) -> Self {
+        QrDecoder {
+            detectors: vec![Detector::new(factory)],
+            preprocessing: None,
+        }
+    }
+
+    /// Also tries the detector made by `factory` when the previous ones read nothing
+    pub fn detector(
+        mut self,
+        factory: impl Fn() -> Box<dyn QrDetector> + Send + Sync + 'static,
+    ) -> Self {
+        self.detectors.push(Detector::new(factory));
+        self
+    }
+
+    /// Tries every backend compiled in, until one of them reads a code
+    pub fn all_backends() -> Self {
+        QrDecoder {
+            detectors: QrBackend::all()
+                .into_iter()
+                .map(|backend| Detector::new(move || backend.detector()))
+                .collect(),
+            preprocessing: None,
+        }
+    }
+
+    /// Retries with preprocessed images when no code can be read from the image as is
+    pub fn preprocessing(mut self, preprocessing: Preprocessing) -> Self {
+        self.preprocessing = Some(preprocessing);
+        self
     }
 
     pub fn read_image(&mut self, image_path: impl AsRef<Path>) -> Result<Vec<u8>, QrError> {
@@ -18,38 +129,161 @@ impl QrDecoder {
         self.image_extract_encrypted(img)
     }
 
+    /// Finds all the QR codes in the image, see [`image_find_codes`](Self::image_find_codes)
+    pub fn read_image_codes(
+        &mut self,
+        image_path: impl AsRef<Path>,
+    ) -> Result<Vec<FoundCode>, QrError> {
+        let img = image::open(image_path)?;
+
+        self.image_find_codes(img)
+    }
+
+    /// Like [`read_image`](Self::read_image), for an encoded image (PNG, JPEG, ...) held in memory
+    pub fn read_image_from_memory(&mut self, data: &[u8]) -> Result<Vec<u8>, QrError> {
+        let img = image::load_from_memory(data)?;
+
+        self.image_extract_encrypted(img)
+    }
+
+    pub fn read_image_codes_from_memory(&mut self, data: &[u8]) -> Result<Vec<FoundCode>, QrError> {
+        let img = image::load_from_memory(data)?;
+
+        self.image_find_codes(img)
+    }
+
+    /// Like [`read_image`](Self::read_image); the whole image is read first to detect its format
+    pub fn read_image_from_reader(&mut self, reader: impl Read) -> Result<Vec<u8>, QrError> {
+        self.read_image_from_memory(&Self::read_all(reader)?)
+    }
+
+    pub fn read_image_codes_from_reader(
+        &mut self,
+        reader: impl Read,
+    ) -> Result<Vec<FoundCode>, QrError> {
+        self.read_image_codes_from_memory(&Self::read_all(reader)?)
+    }
+
+    fn read_all(mut reader: impl Read) -> Result<Vec<u8>, QrError> {
+        let mut data = Vec::new();
+        reader
+            .read_to_end(&mut data)
+            .map_err(image::ImageError::IoError)?;
+
+        Ok(data)
+    }
+
     pub fn image_extract_encrypted(&mut self, image: DynamicImage) -> Result<Vec<u8>, QrError> {
         let code = self.image_get_payload(image)?;
         Self::decode_payload(&code)
     }
 
+    /// Payload of the first readable code, or the first code's error if none is
     pub fn image_get_payload(&mut self, image: DynamicImage) -> Result<String, QrError> {
+        let mut codes = self.image_find_codes(image)?.into_iter();
+        let first = codes.next().ok_or(QrError::NoData)?;
+
+        match first.payload {
+            Ok(payload) => Ok(payload),
+            Err(e) => codes.find_map(|code| code.payload.ok()).ok_or(e),
+        }
+    }
+
+    /// Locates every QR code in the image; an empty list means none were found
+    pub fn image_find_codes(&mut self, image: DynamicImage) -> Result<Vec<FoundCode>, QrError> {
+        Ok(self.image_scan(image)?.codes)
+    }
+
+    /// Like [`image_find_codes`](Self::image_find_codes), also recording every detection attempt
+    ///
+    /// Every backend is tried on the original image, then on each preprocessed one,
+    /// stopping at the first attempt that reads at least one code. A backend failing
+    /// only fails its attempt. If none reads a code, the codes located by the first
+    /// attempt that didn't fail are returned.
+    pub fn image_scan(&mut self, image: DynamicImage) -> Result<Scan, QrError> {
         // convert to gray scale
         let image = image.into_luma8();
 
-        // identify all qr codes
-        let mut codes =
-            self.decoder
-                .identify(image.width() as usize, image.height() as usize, &image);
-
-        // only look up the first one
-        let code = codes.next().ok_or(QrError::NoData)?;
+        let mut chains = vec![Vec::new()];
+        if let Some(preprocessing) = &self.preprocessing {
+            chains.extend(preprocessing.chains().iter().cloned());
+        }
 
-        // see if it's properly extracted
-        let code = code?;
+        let mut attempts = Vec::new();
+        let mut fallback = None;
+
+        for steps in chains {
+            let (prepared, sizes) = preprocess::apply_chain(&image, &steps);
+
+            for Detector { detector, .. } in &mut self.detectors {
+                let mut attempt = Attempt {
+                    backend: detector.name().to_owned(),
+                    steps: steps.clone(),
+                    located: 0,
+                    decoded: 0,
+                    error: None,
+                };
+
+                let codes = match Self::identify(detector.as_mut(), &prepared, &steps, &sizes) {
+                    Ok(codes) => codes,
+                    Err(e) => {
+                        attempt.error = Some(e.to_string());
+                        attempts.push(attempt);
+                        continue;
+                    }
+                };
+
+                attempt.located = codes.len();
+                attempt.decoded = codes.iter().filter(|code| code.payload.is_ok()).count();
+                let succeeded = attempt.succeeded();
+
+                attempts.push(attempt);
+
+                if succeeded {
+                    return Ok(Scan { codes, attempts });
+                }
 
-        // decode the payload
-        let data = code.decode()?;
+                fallback.get_or_insert(codes);
+            }
+        }
 
-        // convert to str from bytes
-        let code = std::str::from_utf8(&data.payload)?;
+        Ok(Scan {
+            codes: fallback.unwrap_or_default(),
+            attempts,
+        })
+    }
 
-        Ok(code.to_string())
+    fn identify(
+        detector: &mut dyn QrDetector,
+        image: &GrayImage,
+        steps: &[Preprocess],
+        sizes: &[Resize],
+    ) -> Result<Vec<FoundCode>, QrError> {
+        // identify all qr codes
+        let codes = detector.detect(image)?;
+
+        let codes = codes
+            .into_iter()
+            .map(|code| FoundCode {
+                // report positions in the original image
+                corners: code
+                    .corners
+                    .map(|p| preprocess::map_back_chain(p, steps, sizes)),
+                payload: code.data.and_then(|data| {
+                    // convert to str from bytes
+                    Ok(std::str::from_utf8(&data)?.to_string())
+                }),
+                preprocessing: steps.to_vec(),
+                backend: detector.name().to_owned(),
+            })
+            .collect();
+
+        Ok(codes)
     }
 
     pub fn decode_payload(code: &str) -> Result<Vec<u8>, QrError> {
         match code.split_once(';') {
-            Some(("1", payload)) => {
+            Some((version, payload)) if version == PAYLOAD_VERSION => {
                 let decoded = base64::decode(payload)?;
 
                 Ok(decoded)
@@ -64,6 +298,32 @@ impl QrDecoder {
             _ => Err(QrError::MalformedPayload),
         }
     }
+
+    /// Inverse of [`decode_payload`](Self::decode_payload)
+    pub fn encode_payload(ciphertext: impl AsRef<[u8]>) -> String {
+        format!("{};{}", PAYLOAD_VERSION, base64::encode(ciphertext))
+    }
+}
+
+impl Default for QrDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for QrDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let detectors: Vec<_> = self
+            .detectors
+            .iter()
+            .map(|d| d.detector.name().to_owned())
+            .collect();
+
+        f.debug_struct("QrDecoder")
+            .field("detectors", &detectors)
+            .field("preprocessing", &self.preprocessing)
+            .finish()
+    }
 }
 
 #[cfg(test)]
@@ -91,6 +351,26 @@ mod tests {
 
             (path, base64, payload, &cipher[..])
         }
+
+        /// Cases 1 and 2 side by side on a single canvas
+        pub fn two_codes() -> DynamicImage {
+            side_by_side(case1().0, case2().0)
+        }
+
+        pub fn side_by_side(left: impl AsRef<Path>, right: impl AsRef<Path>) -> DynamicImage {
+            let left = image::open(left).unwrap().into_luma8();
+            let right = image::open(right).unwrap().into_luma8();
+
+            let mut canvas = image::GrayImage::from_pixel(
+                left.width() + right.width(),
+                left.height().max(right.height()),
+                image::Luma([255]),
+            );
+            image::imageops::overlay(&mut canvas, &left, 0, 0);
+            image::imageops::overlay(&mut canvas, &right, left.width(), 0);
+
+            DynamicImage::ImageLuma8(canvas)
+        }
     }
 
     mod case1 {
@@ -98,7 +378,7 @@ mod tests {
 
         #[test]
         fn full() {
-            let (path, base64, payload, cipher) = data::case1();
+            let (path, _base64, _payload, cipher) = data::case1();
 
             let mut qr = QrDecoder::new();
 
@@ -106,9 +386,71 @@ mod tests {
             check!(decoded == cipher);
         }
 
+        #[test]
+        fn clone() {
+            let (path, _base64, _payload, cipher) = data::case1();
+
+            let qr = QrDecoder::new().preprocessing(Preprocessing::default());
+            let mut clone = qr.clone();
+
+            let_assert!(Ok(decoded) = clone.read_image(path));
+            check!(decoded == cipher);
+            check!(format!("{:?}", clone) == format!("{:?}", qr));
+        }
+
+        #[test]
+        fn clones_own_detectors() {
+            use std::sync::atomic::{AtomicUsize, Ordering};
+
+            fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+            let made = Arc::new(AtomicUsize::new(0));
+            let qr = QrDecoder::with_detector({
+                let made = made.clone();
+                move || {
+                    made.fetch_add(1, Ordering::SeqCst);
+                    QrBackend::default().detector()
+                }
+            });
+            assert_send_sync(&qr);
+            check!(made.load(Ordering::SeqCst) == 1);
+
+            // parallel workers don't wait for each other's detectors
+            let clones = vec![qr.clone(), qr.clone()];
+            check!(made.load(Ordering::SeqCst) == 3);
+
+            let (path, _base64, _payload, cipher) = data::case1();
+            let image = image::open(path).unwrap();
+            std::thread::scope(|scope| {
+                for mut clone in clones {
+                    let image = image.clone();
+                    scope.spawn(move || {
+                        let_assert!(Ok(decoded) = clone.image_extract_encrypted(image));
+                        check!(decoded == cipher);
+                    });
+                }
+            });
+        }
+
+        #[test]
+        fn from_memory() {
+            let (path, _base64, _payload, cipher) = data::case1();
+            let image = std::fs::read(path).expect("Unable to read test data");
+
+            let mut qr = QrDecoder::new();
+
+            let_assert!(Ok(decoded) = qr.read_image_from_memory(&image));
+            check!(decoded == cipher);
+
+            let_assert!(Ok(codes) = qr.read_image_codes_from_reader(&image[..]));
+            check!(codes.len() == 1);
+
+            let_assert!(Err(QrError::Image(_)) = qr.read_image_from_memory(b"not an image"));
+        }
+
         #[test]
         fn payload_from_image() {
-            let (path, base64, payload, cipher) = data::case1();
+            let (path, _base64, payload, _cipher) = data::case1();
 
             let mut qr = QrDecoder::new();
             let img = image::open(path).unwrap();
@@ -119,7 +461,7 @@ mod tests {
 
         #[test]
         fn base64_from_image() {
-            let (path, base64, payload, cipher) = data::case1();
+            let (path, base64, _payload, _cipher) = data::case1();
 
             let mut qr = QrDecoder::new();
             let img = image::open(path).unwrap();
@@ -132,9 +474,16 @@ mod tests {
             check!(decoded_b64 == base64);
         }
 
+        #[test]
+        fn encode_payload() {
+            let (_path, _base64, payload, cipher) = data::case1();
+
+            check!(QrDecoder::encode_payload(cipher) == payload);
+        }
+
         #[test]
         fn cipher_from_image() {
-            let (path, base64, payload, cipher) = data::case1();
+            let (path, _base64, _payload, cipher) = data::case1();
 
             let mut qr = QrDecoder::new();
             let img = image::open(path).unwrap();
@@ -149,7 +498,7 @@ mod tests {
 
         #[test]
         fn full() {
-            let (path, base64, payload, cipher) = data::case2();
+            let (path, _base64, _payload, cipher) = data::case2();
 
             let mut qr = QrDecoder::new();
 
@@ -171,4 +520,265 @@ mod tests {
 
         let_assert!(Err(QrError::NoData) = qr.read_image("testdata/missing_qr.png"));
     }
+
+    mod multiple {
+        use super::*;
+        use image::GenericImageView;
+
+        #[test]
+        fn find_codes() {
+            let (_path, _base64, payload1, _cipher) = data::case1();
+            let (_path, _base64, payload2, _cipher) = data::case2();
+            let image = data::two_codes();
+            let half = image.width() as i32 / 2;
+
+            let mut qr = QrDecoder::new();
+
+            let_assert!(Ok(mut codes) = qr.image_find_codes(image));
+            check!(codes.len() == 2);
+
+            codes.sort_by_key(|code| code.corners[0].x);
+            check!(codes[0].corners.iter().all(|p| p.x < half));
+            check!(codes[1].corners.iter().all(|p| p.x >= half));
+
+            let_assert!(Ok(decoded1) = &codes[0].payload);
+            let_assert!(Ok(decoded2) = &codes[1].payload);
+            check!(decoded1 == payload1);
+            check!(decoded2 == payload2);
+        }
+
+        #[test]
+        fn into_encrypted() {
+            let (path, _base64, _payload, cipher) = data::case1();
+
+            let mut qr = QrDecoder::new();
+
+            let_assert!(Ok(mut codes) = qr.read_image_codes(path));
+            check!(codes.len() == 1);
+            let_assert!(Ok(decoded) = codes.remove(0).into_encrypted());
+            check!(decoded == cipher);
+        }
+
+        #[test]
+        fn malformed_qr() {
+            let mut qr = QrDecoder::new();
+
+            let_assert!(Ok(codes) = qr.read_image_codes("testdata/malformed_qr.png"));
+            check!(codes.len() == 1);
+            let_assert!(Err(QrError::Decode(_)) = &codes[0].payload);
+        }
+
+        #[test]
+        fn missing_qr() {
+            let mut qr = QrDecoder::new();
+
+            let_assert!(Ok(codes) = qr.read_image_codes("testdata/missing_qr.png"));
+            check!(codes.is_empty());
+        }
+
+        #[test]
+        fn damaged_and_valid() {
+            let (path, _base64, payload, _cipher) = data::case1();
+            let image = data::side_by_side("testdata/malformed_qr.png", path);
+
+            let mut qr = QrDecoder::new();
+
+            let_assert!(Ok(mut codes) = qr.image_find_codes(image.clone()));
+            codes.sort_by_key(|code| code.corners[0].x);
+            let_assert!([damaged, valid] = &codes[..]);
+            let_assert!(Err(QrError::Decode(_)) = &damaged.payload);
+            let_assert!(Ok(decoded) = &valid.payload);
+            check!(decoded == payload);
+
+            let_assert!(Ok(decoded) = qr.image_get_payload(image));
+            check!(decoded == payload);
+        }
+    }
+
+    mod preprocessing {
+        use super::*;
+
+        fn inverted(path: impl AsRef<Path>) -> DynamicImage {
+            let mut image = image::open(path).unwrap();
+            image.invert();
+            image
+        }
+
+        #[test]
+        fn not_needed() {
+            let (path, _base64, _payload, _cipher) = data::case1();
+
+            let mut qr = QrDecoder::new().preprocessing(Preprocessing::default());
+
+            let_assert!(Ok(scan) = qr.image_scan(image::open(path).unwrap()));
+            check!(scan.attempts.len() == 1);
+            check!(scan.codes[0].preprocessing.is_empty());
+        }
+
+        #[test]
+        fn inverted_colors() {
+            let (path, _base64, payload, _cipher) = data::case1();
+
+            let mut plain = QrDecoder::new();
+            let_assert!(Ok(codes) = plain.image_find_codes(inverted(&path)));
+            check!(codes.iter().all(|code| code.payload.is_err()));
+
+            let mut qr = QrDecoder::new().preprocessing(Preprocessing::default());
+
+            let_assert!(Ok(scan) = qr.image_scan(inverted(&path)));
+            check!(scan.attempts.len() > 1);
+            check!(!scan.attempts[0].succeeded());
+            check!(scan.attempts.last().unwrap().succeeded());
+            check!(scan.attempts[1..scan.attempts.len() - 1]
+                .iter()
+                .all(|attempt| !attempt.succeeded()));
+
+            let_assert!([code] = &scan.codes[..]);
+            let_assert!(Ok(decoded) = &code.payload);
+            check!(decoded == payload);
+            check!(code.preprocessing.contains(&Preprocess::Invert));
+        }
+
+        #[test]
+        fn custom_chain() {
+            let (path, _base64, payload, _cipher) = data::case1();
+
+            let preprocessing = Preprocessing::none().chain([Preprocess::Invert]);
+            let mut qr = QrDecoder::new().preprocessing(preprocessing);
+
+            let_assert!(Ok(decoded) = qr.image_get_payload(inverted(&path)));
+            check!(decoded == payload);
+        }
+
+        #[test]
+        fn gives_up() {
+            let mut qr = QrDecoder::new().preprocessing(Preprocessing::default());
+
+            let img = image::open("testdata/missing_qr.png").unwrap();
+
+            let_assert!(Ok(scan) = qr.image_scan(img));
+            check!(scan.codes.is_empty());
+            check!(scan.attempts.len() == Preprocessing::default().chains().len() + 1);
+        }
+    }
+
+    mod backends {
+        use super::*;
+        use crate::qr_backend::DetectedCode;
+
+        /// Detector that never finds anything
+        struct Blind;
+
+        impl QrDetector for Blind {
+            fn name(&self) -> &str {
+                "blind"
+            }
+
+            fn detect(&mut self, _image: &GrayImage) -> Result<Vec<DetectedCode>, QrError> {
+                Ok(Vec::new())
+            }
+        }
+
+        /// Detector that always fails
+        struct Broken;
+
+        impl QrDetector for Broken {
+            fn name(&self) -> &str {
+                "broken"
+            }
+
+            fn detect(&mut self, _image: &GrayImage) -> Result<Vec<DetectedCode>, QrError> {
+                Err(quircs::ExtractError::OutOfBounds.into())
+            }
+        }
+
+        #[test]
+        fn every_backend() {
+            let (path, _base64, payload, _cipher) = data::case1();
+
+            for backend in QrBackend::all() {
+                let mut qr = QrDecoder::with_backend(backend);
+
+                let_assert!(Ok(codes) = qr.read_image_codes(&path));
+                let_assert!([code] = &codes[..]);
+                let_assert!(Ok(decoded) = &code.payload);
+                check!(decoded == payload);
+                check!(code.backend == backend.name());
+            }
+        }
+
+        #[test]
+        fn missing_qr() {
+            for backend in QrBackend::all() {
+                let mut qr = QrDecoder::with_backend(backend);
+
+                let_assert!(Err(QrError::NoData) = qr.read_image("testdata/missing_qr.png"));
+            }
+        }
+
+        #[test]
+        fn fallback() {
+            let (path, _base64, payload, _cipher) = data::case1();
+
+            let mut qr = QrDecoder::with_detector(|| Box::new(Blind))
+                .detector(|| QrBackend::default().detector());
+
+            let_assert!(Ok(scan) = qr.image_scan(image::open(path).unwrap()));
+            check!(scan.attempts.len() == 2);
+            check!(scan.attempts[0].backend == "blind");
+            check!(!scan.attempts[0].succeeded());
+            check!(scan.attempts[1].succeeded());
+
+            let_assert!(Ok(decoded) = &scan.codes[0].payload);
+            check!(decoded == payload);
+        }
+
+        #[test]
+        fn failing_backend() {
+            let (path, _base64, payload, _cipher) = data::case1();
+
+            let mut qr = QrDecoder::with_detector(|| Box::new(Broken))
+                .detector(|| QrBackend::default().detector());
+
+            let_assert!(Ok(scan) = qr.image_scan(image::open(&path).unwrap()));
+            let_assert!([failed, succeeded] = &scan.attempts[..]);
+            check!(failed.backend == "broken");
+            check!(failed.error.as_deref() == Some("QR code extraction failed: Out of bounds"));
+            check!(succeeded.succeeded());
+            let_assert!(Ok(decoded) = &scan.codes[0].payload);
+            check!(decoded == payload);
+
+            // the preprocessing chains still run
+            let preprocessing = Preprocessing::none().chain([Preprocess::Invert]);
+            let mut qr = QrDecoder::with_detector(|| Box::new(Broken))
+                .detector(|| QrBackend::default().detector())
+                .preprocessing(preprocessing);
+
+            let mut image = image::open(&path).unwrap();
+            image.invert();
+            let_assert!(Ok(scan) = qr.image_scan(image));
+            check!(scan.attempts.len() == 4);
+            check!(scan.attempts[2].error.is_some());
+            check!(scan.attempts[3].succeeded());
+            check!(scan.codes[0].preprocessing == [Preprocess::Invert]);
+        }
+
+        #[test]
+        fn all_backends() {
+            let mut qr = QrDecoder::all_backends();
+
+            let img = image::open("testdata/missing_qr.png").unwrap();
+
+            let_assert!(Ok(scan) = qr.image_scan(img));
+            check!(scan.attempts.len() == QrBackend::all().len());
+        }
+
+        #[test]
+        fn from_str() {
+            for backend in QrBackend::all() {
+                check!(backend.name().parse() == Ok(backend));
+            }
+            check!("zxing".parse::<QrBackend>().is_err());
+        }
+    }
 }
@@ -0,0 +1,78 @@
+use crate::error::QrError;
+use crate::qr::Point;
+use image::GrayImage;
+use std::fmt;
+use std::str::FromStr;
+
+mod quircs;
+#[cfg(feature = "rqrr")]
+mod rqrr;
+
+/// QR code located by a [`QrDetector`], with the raw payload bytes
+#[derive(Debug)]
+pub struct DetectedCode {
+    /// Corners of the code, clockwise from the top left one; all zero if it couldn't be extracted
+    pub corners: [Point; 4],
+    /// Payload, or why this code couldn't be read
+    pub data: Result<Vec<u8>, QrError>,
+}
+
+/// Locates and decodes QR codes; failures are reported with the [`QrError`] variants
+pub trait QrDetector: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn detect(&mut self, image: &GrayImage) -> Result<Vec<DetectedCode>, QrError>;
+}
+
+/// QR code detection implementation; the available ones depend on the enabled cargo features
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum QrBackend {
+    #[default]
+    Quircs,
+    #[cfg(feature = "rqrr")]
+    Rqrr,
+}
+
+impl QrBackend {
+    /// Every backend compiled in, the default one first
+    pub fn all() -> Vec<QrBackend> {
+        vec![
+            QrBackend::Quircs,
+            #[cfg(feature = "rqrr")]
+            QrBackend::Rqrr,
+        ]
+    }
+
+    pub fn detector(self) -> Box<dyn QrDetector> {
+        match self {
+            QrBackend::Quircs => Box::new(::quircs::Quirc::default()),
+            #[cfg(feature = "rqrr")]
+            QrBackend::Rqrr => Box::new(rqrr::RqrrDetector),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            QrBackend::Quircs => "quircs",
+            #[cfg(feature = "rqrr")]
+            QrBackend::Rqrr => "rqrr",
+        }
+    }
+}
+
+impl fmt::Display for QrBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for QrBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        QrBackend::all()
+            .into_iter()
+            .find(|backend| backend.name() == s)
+            .ok_or_else(|| format!("unknown QR backend {}", s))
+    }
+}
@@ -0,0 +1,34 @@
+use super::{DetectedCode, QrDetector};
+use crate::error::QrError;
+use crate::qr::Point;
+use image::GrayImage;
+use quircs::Quirc;
+
+impl QrDetector for Quirc {
+    fn name(&self) -> &str {
+        "quircs"
+    }
+
+    fn detect(&mut self, image: &GrayImage) -> Result<Vec<DetectedCode>, QrError> {
+        let codes = self.identify(image.width() as usize, image.height() as usize, image);
+
+        // a code failing to extract mustn't hide the others
+        let codes = codes
+            .map(|code| match code {
+                Ok(code) => DetectedCode {
+                    corners: code.corners.map(|p| Point { x: p.x, y: p.y }),
+                    data: code
+                        .decode()
+                        .map(|data| data.payload)
+                        .map_err(QrError::from),
+                },
+                Err(e) => DetectedCode {
+                    corners: Default::default(),
+                    data: Err(e.into()),
+                },
+            })
+            .collect();
+
+        Ok(codes)
+    }
+}
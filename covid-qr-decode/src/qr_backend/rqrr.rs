@@ -0,0 +1,59 @@
+use super::{DetectedCode, QrDetector};
+use crate::error::QrError;
+use crate::qr::Point;
+use image::GrayImage;
+use quircs::DecodeError;
+use rqrr::{DeQRError, PreparedImage};
+
+/// Detector based on the `rqrr` crate, which copes better with some blurry and skewed codes
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct RqrrDetector;
+
+// both crates are ports of quirc, so the failure modes match
+fn decode_error(e: DeQRError) -> QrError {
+    match e {
+        DeQRError::InvalidGridSize => DecodeError::InvalidGridSize.into(),
+        DeQRError::InvalidVersion => DecodeError::InvalidVersion.into(),
+        DeQRError::DataEcc => DecodeError::DataEcc.into(),
+        DeQRError::FormatEcc => DecodeError::FormatEcc.into(),
+        DeQRError::UnknownDataType => DecodeError::UnkownDataType.into(),
+        DeQRError::DataOverflow => DecodeError::DataOverflow.into(),
+        DeQRError::DataUnderflow => DecodeError::DataUnderflow.into(),
+        // only raised when writing to the output fails, which a Vec doesn't
+        e @ DeQRError::IoError => QrError::Rqrr(e),
+        // raw bytes are requested, so UTF-8 isn't checked here
+        DeQRError::EncodingError => QrError::MalformedPayload,
+    }
+}
+
+impl QrDetector for RqrrDetector {
+    fn name(&self) -> &str {
+        "rqrr"
+    }
+
+    fn detect(&mut self, image: &GrayImage) -> Result<Vec<DetectedCode>, QrError> {
+        let mut prepared = PreparedImage::prepare_from_greyscale(
+            image.width() as usize,
+            image.height() as usize,
+            |x, y| image.get_pixel(x as u32, y as u32)[0],
+        );
+
+        let codes = prepared
+            .detect_grids()
+            .into_iter()
+            .map(|grid| {
+                let mut data = Vec::new();
+
+                DetectedCode {
+                    corners: grid.bounds.map(|p| Point { x: p.x, y: p.y }),
+                    data: grid
+                        .decode_to(&mut data)
+                        .map(|_| data)
+                        .map_err(decode_error),
+                }
+            })
+            .collect();
+
+        Ok(codes)
+    }
+}
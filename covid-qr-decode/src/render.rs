@@ -0,0 +1,172 @@
+use crate::error::RenderError;
+use image::{DynamicImage, ImageOutputFormat, Luma};
+use qrcode::render::{svg, unicode::Dense1x2};
+use qrcode::{EcLevel, QrCode};
+use std::io::Write;
+use std::str::FromStr;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorCorrection {
+    Low,
+    #[default]
+    Medium,
+    Quartile,
+    High,
+}
+
+impl FromStr for ErrorCorrection {
+    type Err = RenderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "L" | "l" | "low" => Ok(ErrorCorrection::Low),
+            "M" | "m" | "medium" => Ok(ErrorCorrection::Medium),
+            "Q" | "q" | "quartile" => Ok(ErrorCorrection::Quartile),
+            "H" | "h" | "high" => Ok(ErrorCorrection::High),
+            _ => Err(RenderError::UnknownErrorCorrection(s.to_owned())),
+        }
+    }
+}
+
+impl From<ErrorCorrection> for EcLevel {
+    fn from(ec: ErrorCorrection) -> Self {
+        match ec {
+            ErrorCorrection::Low => EcLevel::L,
+            ErrorCorrection::Medium => EcLevel::M,
+            ErrorCorrection::Quartile => EcLevel::Q,
+            ErrorCorrection::High => EcLevel::H,
+        }
+    }
+}
+
+/// Renders QR code payloads (`1;<base64>`) back to images
+pub struct QrRenderer {
+    code: QrCode,
+    module_size: u32,
+}
+
+impl QrRenderer {
+    const DEFAULT_MODULE_SIZE: u32 = 8;
+
+    pub fn new(payload: &str, ec: ErrorCorrection) -> Result<Self, RenderError> {
+        let code = QrCode::with_error_correction_level(payload, ec.into())?;
+
+        Ok(QrRenderer {
+            code,
+            module_size: Self::DEFAULT_MODULE_SIZE,
+        })
+    }
+
+    /// Size of a single module in pixels (PNG) or user units (SVG)
+    pub fn module_size(mut self, module_size: u32) -> Self {
+        self.module_size = module_size.max(1);
+        self
+    }
+
+    pub fn to_image(&self) -> DynamicImage {
+        let image = self
+            .code
+            .render::<Luma<u8>>()
+            .module_dimensions(self.module_size, self.module_size)
+            .build();
+
+        DynamicImage::ImageLuma8(image)
+    }
+
+    pub fn write_png(&self, mut writer: impl Write) -> Result<(), RenderError> {
+        self.to_image()
+            .write_to(&mut writer, ImageOutputFormat::Png)?;
+
+        Ok(())
+    }
+
+    pub fn to_svg(&self) -> String {
+        self.code
+            .render::<svg::Color>()
+            .module_dimensions(self.module_size, self.module_size)
+            .build()
+    }
+
+    /// Unicode half-block rendering, two modules per character cell;
+    /// light modules are drawn as blocks, which suits dark terminal backgrounds
+    pub fn to_terminal(&self) -> String {
+        self.code
+            .render::<Dense1x2>()
+            .dark_color(Dense1x2::Light)
+            .light_color(Dense1x2::Dark)
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::QrDecoder;
+    use assert2::{check, let_assert};
+
+    mod data {
+        pub fn case1() -> &'static str {
+            include_str!("../testdata/1.payload")
+        }
+    }
+
+    #[test]
+    fn image_round_trip() {
+        let payload = data::case1();
+
+        let_assert!(Ok(renderer) = QrRenderer::new(payload, ErrorCorrection::Low));
+
+        let mut qr = QrDecoder::new();
+
+        let_assert!(Ok(decoded) = qr.image_get_payload(renderer.module_size(4).to_image()));
+        check!(decoded == payload);
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let payload = data::case1();
+
+        let_assert!(Ok(renderer) = QrRenderer::new(payload, ErrorCorrection::High));
+
+        let mut png = Vec::new();
+        let_assert!(Ok(()) = renderer.write_png(&mut png));
+        let_assert!(Ok(image) = image::load_from_memory(&png));
+
+        let mut qr = QrDecoder::new();
+
+        let_assert!(Ok(decoded) = qr.image_get_payload(image));
+        check!(decoded == payload);
+    }
+
+    #[test]
+    fn svg() {
+        let_assert!(Ok(renderer) = QrRenderer::new(data::case1(), ErrorCorrection::Medium));
+
+        let svg = renderer.to_svg();
+
+        check!(svg.contains("<svg"));
+    }
+
+    #[test]
+    fn terminal() {
+        let_assert!(Ok(renderer) = QrRenderer::new(data::case1(), ErrorCorrection::Medium));
+
+        let text = renderer.to_terminal();
+
+        check!(text.lines().count() > 1);
+        check!(text.contains('\u{2588}'));
+    }
+
+    #[test]
+    fn error_correction() {
+        check!(ErrorCorrection::from_str("Q").ok() == Some(ErrorCorrection::Quartile));
+        let_assert!(Err(RenderError::UnknownErrorCorrection(_)) = ErrorCorrection::from_str("X"));
+    }
+
+    #[test]
+    fn too_long() {
+        let payload = "x".repeat(8000);
+
+        let_assert!(Err(RenderError::Qr(_)) = QrRenderer::new(&payload, ErrorCorrection::High));
+    }
+}
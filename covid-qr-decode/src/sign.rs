@@ -0,0 +1,139 @@
+use crate::backend::{CryptoBackend, MessageSigner};
+use crate::error::SigningError;
+use crate::{QrDecoder, VaccinationInfo};
+#[cfg(feature = "openssl")]
+use openssl::{pkey::Private, rsa::Rsa};
+
+/// Inverse of [`RsaDecrypter`](crate::RsaDecrypter); signs records with a private key
+pub struct RsaSigner {
+    key: Box<dyn MessageSigner>,
+}
+
+impl RsaSigner {
+    #[cfg(feature = "openssl")]
+    pub fn new(key: Rsa<Private>) -> Self {
+        RsaSigner { key: Box::new(key) }
+    }
+
+    pub fn with_backend(key: Box<dyn MessageSigner>) -> Self {
+        RsaSigner { key }
+    }
+
+    pub fn from_pem(pem: impl AsRef<[u8]>) -> Result<Self, SigningError> {
+        Self::from_pem_with(pem, CryptoBackend::default())
+    }
+
+    pub fn from_pem_with(
+        pem: impl AsRef<[u8]>,
+        backend: CryptoBackend,
+    ) -> Result<Self, SigningError> {
+        let key = backend.private_key_from_pem(pem.as_ref())?;
+
+        Ok(RsaSigner { key })
+    }
+
+    pub fn sign(&self, plaintext: impl AsRef<[u8]>) -> Result<Vec<u8>, SigningError> {
+        self.key.sign(plaintext.as_ref())
+    }
+
+    /// Produces the `1;<base64>` QR code payload, as read by [`QrDecoder::decode_payload`](crate::QrDecoder::decode_payload)
+    pub fn sign_payload(&self, info: &VaccinationInfo) -> Result<String, SigningError> {
+        let ciphertext = self.sign(info.to_record())?;
+
+        Ok(QrDecoder::encode_payload(ciphertext))
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl From<Rsa<Private>> for RsaSigner {
+    fn from(key: Rsa<Private>) -> Self {
+        RsaSigner::new(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RsaDecrypter;
+    use assert2::{check, let_assert};
+    use std::str::FromStr;
+
+    mod data {
+        use super::*;
+
+        pub fn signer(backend: CryptoBackend) -> RsaSigner {
+            let pem = include_bytes!("../keys/test_signing_private.pem");
+
+            RsaSigner::from_pem_with(&pem[..], backend).expect("Malformed test RSA key")
+        }
+
+        pub fn decrypter(backend: CryptoBackend) -> RsaDecrypter {
+            let pem = include_bytes!("../keys/test_signing_public.pem");
+
+            RsaDecrypter::from_pem_with(&pem[..], backend).expect("Malformed test RSA key")
+        }
+
+        pub fn backends() -> Vec<CryptoBackend> {
+            vec![
+                #[cfg(feature = "openssl")]
+                CryptoBackend::OpenSsl,
+                #[cfg(feature = "rust-crypto")]
+                CryptoBackend::RustCrypto,
+            ]
+        }
+
+        pub fn case1() -> &'static str {
+            include_str!("../testdata/1.plain").trim_end()
+        }
+    }
+
+    #[test]
+    fn sign() {
+        let plain = data::case1();
+
+        // every backend has to verify signatures made by every other one
+        for signer in data::backends() {
+            for verifier in data::backends() {
+                let_assert!(Ok(cipher) = data::signer(signer).sign(plain));
+                let_assert!(Ok(dec_plain) = data::decrypter(verifier).decrypt(cipher));
+                check!(dec_plain == plain);
+            }
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        let plain = data::case1();
+
+        let signatures = data::backends()
+            .into_iter()
+            .map(|backend| data::signer(backend).sign(plain).ok())
+            .collect::<Vec<_>>();
+
+        check!(signatures
+            .iter()
+            .all(|s| s.is_some() && *s == signatures[0]));
+    }
+
+    #[test]
+    fn payload() {
+        let_assert!(Ok(info) = VaccinationInfo::from_str(data::case1()));
+
+        let signer = data::signer(CryptoBackend::default());
+        let dec = data::decrypter(CryptoBackend::default());
+
+        let_assert!(Ok(payload) = signer.sign_payload(&info));
+        check!(payload.starts_with("1;"));
+
+        let_assert!(Ok(cipher) = QrDecoder::decode_payload(&payload));
+        let_assert!(Ok(dec_plain) = dec.decrypt(cipher));
+        check!(VaccinationInfo::from_str(&dec_plain) == Ok(info));
+    }
+
+    #[test]
+    fn malformed_key() {
+        for backend in data::backends() {
+            let_assert!(Err(_) = RsaSigner::from_pem_with("not a key", backend));
+        }
+    }
+}
@@ -1,11 +1,138 @@
 use chrono::naive::NaiveDate;
+use chrono::{DateTime, Datelike, TimeZone};
 use std::convert::Infallible;
+use std::fmt;
 use std::str;
 use std::str::FromStr;
 
+use crate::clock::{certificate_date, Clock, SystemClock};
 use crate::error::{FieldName, MalformedLine};
+use crate::vaccine::VaccineType;
+
+static DATE_FORMAT: &str = "%d-%m-%Y";
+static FIELD_SEPARATOR: char = ';';
+
+/// Day and month of the holder's birth; the record doesn't carry the year
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "serde_impl::RawShortBirthdate")
+)]
+pub struct ShortBirthdate {
+    month: u32,
+    day: u32,
+}
+
+impl ShortBirthdate {
+    /// Returns `None` if the day doesn't exist in the given month (in a leap year)
+    pub fn new(month: u32, day: u32) -> Option<Self> {
+        // year 0 is a leap year in the proleptic Gregorian calendar,
+        // so 29-02 is accepted
+        NaiveDate::from_ymd_opt(0, month, day).map(|_| ShortBirthdate { month, day })
+    }
+
+    pub fn month(&self) -> u32 {
+        self.month
+    }
+
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+
+    /// Full birth date, if the holder was born in `year`
+    pub fn with_year(&self, year: i32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(year, self.month, self.day)
+    }
+}
+
+impl fmt::Display for ShortBirthdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}-{:02}", self.day, self.month)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::{ShortBirthdate, VaccinationInfo, FIELD_SEPARATOR};
+    use crate::error::{FieldName, MalformedLine};
+    use chrono::NaiveDate;
+    use std::convert::TryFrom;
+
+    #[derive(serde::Deserialize)]
+    pub(super) struct RawShortBirthdate {
+        month: u32,
+        day: u32,
+    }
+
+    impl TryFrom<RawShortBirthdate> for ShortBirthdate {
+        type Error = String;
+
+        fn try_from(raw: RawShortBirthdate) -> Result<Self, Self::Error> {
+            ShortBirthdate::new(raw.month, raw.day)
+                .ok_or_else(|| format!("invalid short birthdate {:02}-{:02}", raw.day, raw.month))
+        }
+    }
+
+    #[derive(serde::Deserialize)]
+    pub(super) struct RawVaccinationInfo {
+        id: u64,
+        version: u8,
+        issue_date: NaiveDate,
+        names: String,
+        first_surname_letter: char,
+        short_birthdate: ShortBirthdate,
+        certificate_expiration: NaiveDate,
+        vaccine_type: String,
+    }
+
+    impl TryFrom<RawVaccinationInfo> for VaccinationInfo {
+        type Error = MalformedLine;
+
+        /// Accepts only what [`VaccinationInfo::from_str`] could have parsed
+        fn try_from(raw: RawVaccinationInfo) -> Result<Self, Self::Error> {
+            let check = |valid: bool, field: FieldName| {
+                if valid {
+                    Ok(())
+                } else {
+                    Err(MalformedLine::MalformedFieldData(field))
+                }
+            };
+
+            // only version 1 is supported
+            check(raw.version == 1, FieldName::Version)?;
+            check(!raw.names.contains(FIELD_SEPARATOR), FieldName::Names)?;
+            check(
+                raw.first_surname_letter != FIELD_SEPARATOR,
+                FieldName::FirstSurnameLetter,
+            )?;
+            // a line ending would be taken for the end of the record
+            check(
+                !raw.vaccine_type.contains(FIELD_SEPARATOR)
+                    && !raw.vaccine_type.ends_with(['\r', '\n']),
+                FieldName::VaccineType,
+            )?;
+
+            Ok(VaccinationInfo {
+                id: raw.id,
+                version: raw.version,
+                issue_date: raw.issue_date,
+                names: raw.names,
+                first_surname_letter: raw.first_surname_letter,
+                short_birthdate: raw.short_birthdate,
+                certificate_expiration: raw.certificate_expiration,
+                vaccine_type: raw.vaccine_type,
+            })
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "serde_impl::RawVaccinationInfo")
+)]
 pub struct VaccinationInfo {
     // szczepienieId – identyfikator szczepienia (identyfikator zasobu Immunization),
     id: u64,
@@ -18,21 +145,103 @@ pub struct VaccinationInfo {
     // pierwszaLiteraNazwiska – pierwsza litera nazwiska posiadacza dowodu szczepienia,
     first_surname_letter: char,
     // skroconaDataUrodzenia – skrócona data urodzenia posiadacza dowodu szczepienia,
-    short_birthdate: NaiveDate,
+    short_birthdate: ShortBirthdate,
     // dataWaznosciDowodu – data ważności dowodu,
     certificate_expiration: NaiveDate,
     // danaTechniczna – oznaczenie szczepionki
     vaccine_type: String,
 }
 
+/// Borrowed view of all the [`VaccinationInfo`] fields
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct VaccinationInfoView<'a> {
+    pub id: u64,
+    pub version: u8,
+    pub issue_date: NaiveDate,
+    pub names: &'a str,
+    pub first_surname_letter: char,
+    pub short_birthdate: ShortBirthdate,
+    pub certificate_expiration: NaiveDate,
+    pub vaccine_type: &'a str,
+}
+
 impl VaccinationInfo {
+    /// szczepienieId
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// wersjaZasobu
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// dataWydania
+    pub fn issue_date(&self) -> NaiveDate {
+        self.issue_date
+    }
+
+    /// imiona
+    pub fn names(&self) -> &str {
+        &self.names
+    }
+
+    /// pierwszaLiteraNazwiska
+    pub fn first_surname_letter(&self) -> char {
+        self.first_surname_letter
+    }
+
+    /// skroconaDataUrodzenia
+    pub fn short_birthdate(&self) -> ShortBirthdate {
+        self.short_birthdate
+    }
+
+    /// dataWaznosciDowodu
+    pub fn certificate_expiration(&self) -> NaiveDate {
+        self.certificate_expiration
+    }
+
+    /// danaTechniczna
+    pub fn vaccine_type(&self) -> &str {
+        &self.vaccine_type
+    }
+
+    /// Vaccine product told by the built-in `danaTechniczna` codes
+    pub fn vaccine(&self) -> VaccineType {
+        VaccineType::from_code(&self.vaccine_type)
+    }
+
+    pub fn view(&self) -> VaccinationInfoView<'_> {
+        VaccinationInfoView {
+            id: self.id,
+            version: self.version,
+            issue_date: self.issue_date,
+            names: &self.names,
+            first_surname_letter: self.first_surname_letter,
+            short_birthdate: self.short_birthdate,
+            certificate_expiration: self.certificate_expiration,
+            vaccine_type: &self.vaccine_type,
+        }
+    }
+
+    /// Formats the record back to the `szczepienieId;wersjaZasobu;...` wire format
+    pub fn to_record(&self) -> String {
+        self.to_string()
+    }
+
+    /// Whether the certificate has expired by today, in Polish time
     pub fn has_expired(&self) -> bool {
-        // this may not be very accurate if you're in a different time zone
-        // but then you probably don't need the check anyway
-        // ideally, this should use a proper time zone for the checks
-        let today = chrono::offset::Local::today().naive_local();
+        self.has_expired_on(SystemClock.today())
+    }
+
+    /// Whether the certificate has expired by `date`; it's still valid on its expiration date
+    pub fn has_expired_on(&self, date: NaiveDate) -> bool {
+        self.certificate_expiration < date
+    }
 
-        self.certificate_expiration < today
+    /// Whether the certificate has expired by `instant`, which is taken in Polish time
+    pub fn has_expired_at<T: TimeZone>(&self, instant: &DateTime<T>) -> bool {
+        self.has_expired_on(certificate_date(instant))
     }
 }
 
@@ -49,15 +258,34 @@ fn munch<'a, T, E>(
     }
 }
 
+impl fmt::Display for VaccinationInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sep = FIELD_SEPARATOR;
+
+        write!(
+            f,
+            "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}",
+            self.id,
+            self.version,
+            self.issue_date.format(DATE_FORMAT),
+            self.names,
+            self.first_surname_letter,
+            self.short_birthdate,
+            self.certificate_expiration.format(DATE_FORMAT),
+            self.vaccine_type,
+            sep = sep,
+        )
+    }
+}
+
 impl FromStr for VaccinationInfo {
     type Err = MalformedLine;
 
+    /// Parses a single record; its line ending, if any, isn't part of the last field
     fn from_str(line: &str) -> Result<Self, Self::Err> {
-        static DATE_FORMAT: &str = "%d-%m-%Y";
-
-        let mut s = line.split(';');
+        let mut s = line.trim_end_matches(['\r', '\n']).split(FIELD_SEPARATOR);
 
-        let id = munch(FieldName::Id, &mut s, |id| u64::from_str(id))?;
+        let id = munch(FieldName::Id, &mut s, u64::from_str)?;
         let version = munch(FieldName::Version, &mut s, |v| {
             let version = u8::from_str(v).map_err(|_| ())?;
 
@@ -79,8 +307,9 @@ impl FromStr for VaccinationInfo {
         })?;
         let short_birthdate = munch(FieldName::ShortBirthdate, &mut s, |d| {
             let source = format!("{}-0", d);
+            let date = NaiveDate::parse_from_str(&source, DATE_FORMAT).map_err(|_| ())?;
 
-            NaiveDate::parse_from_str(&source, DATE_FORMAT)
+            ShortBirthdate::new(date.month(), date.day()).ok_or(())
         })?;
         let certificate_expiration = munch(FieldName::CertificateExpiration, &mut s, |d| {
             NaiveDate::parse_from_str(d, DATE_FORMAT)
@@ -116,17 +345,17 @@ mod tests {
         fn parse() -> Result<(), MalformedLine> {
             static LINE: &str = "123456;1;20-01-2021;Anna Kowalska;M;17-04;20-01-2022;321";
 
-            let parsed = VaccinationInfo::from_str(&LINE)?;
+            let parsed = VaccinationInfo::from_str(LINE)?;
 
             check!(
                 VaccinationInfo {
                     id: 123456,
                     version: 1,
-                    issue_date: NaiveDate::from_ymd(2021, 01, 20),
+                    issue_date: NaiveDate::from_ymd(2021, 1, 20),
                     names: "Anna Kowalska".to_owned(),
                     first_surname_letter: 'M',
-                    short_birthdate: NaiveDate::from_ymd(0, 4, 17),
-                    certificate_expiration: NaiveDate::from_ymd(2022, 01, 20),
+                    short_birthdate: ShortBirthdate { month: 4, day: 17 },
+                    certificate_expiration: NaiveDate::from_ymd(2022, 1, 20),
                     vaccine_type: "321".to_owned(),
                 } == parsed
             );
@@ -134,6 +363,212 @@ mod tests {
             Ok(())
         }
 
+        #[test]
+        fn accessors() -> Result<(), MalformedLine> {
+            static LINE: &str = "123456;1;20-01-2021;Anna Kowalska;M;29-02;20-01-2022;321";
+
+            let parsed = VaccinationInfo::from_str(LINE)?;
+
+            check!(parsed.id() == 123456);
+            check!(parsed.version() == 1);
+            check!(parsed.issue_date() == NaiveDate::from_ymd(2021, 1, 20));
+            check!(parsed.names() == "Anna Kowalska");
+            check!(parsed.first_surname_letter() == 'M');
+            check!(parsed.short_birthdate().month() == 2);
+            check!(parsed.short_birthdate().day() == 29);
+            check!(parsed.certificate_expiration() == NaiveDate::from_ymd(2022, 1, 20));
+            check!(parsed.vaccine_type() == "321");
+            check!(parsed.vaccine() == VaccineType::Unknown("321".to_owned()));
+
+            let view = parsed.view();
+
+            check!(view.names == parsed.names());
+            check!(view.short_birthdate == parsed.short_birthdate());
+
+            Ok(())
+        }
+
+        #[test]
+        fn line_ending() -> Result<(), MalformedLine> {
+            for line in &[
+                "123456;1;20-01-2021;Anna Kowalska;M;17-04;20-01-2022;321\n",
+                "123456;1;20-01-2021;Anna Kowalska;M;17-04;20-01-2022;321\r\n",
+            ] {
+                let parsed = VaccinationInfo::from_str(line)?;
+
+                check!(parsed.vaccine_type() == "321");
+                check!(parsed.to_record() == line.trim_end());
+            }
+
+            Ok(())
+        }
+
+        #[test]
+        fn expiration() -> Result<(), MalformedLine> {
+            static LINE: &str = "123456;1;20-01-2021;Anna Kowalska;M;17-04;20-01-2022;321";
+
+            let parsed = VaccinationInfo::from_str(LINE)?;
+
+            check!(!parsed.has_expired_on(NaiveDate::from_ymd(2022, 1, 20)));
+            check!(parsed.has_expired_on(NaiveDate::from_ymd(2022, 1, 21)));
+
+            // already the 21st in Poland
+            check!(parsed.has_expired_at(&chrono::Utc.ymd(2022, 1, 20).and_hms(23, 30, 0)));
+            check!(!parsed.has_expired_at(&chrono::Utc.ymd(2022, 1, 20).and_hms(22, 30, 0)));
+
+            Ok(())
+        }
+
+        mod format {
+            use super::*;
+            use proptest::prelude::*;
+
+            #[test]
+            fn record() -> Result<(), MalformedLine> {
+                static LINE: &str = "123456;1;05-01-2021;Anna Kowalska;M;07-04;20-01-2022;321";
+
+                let parsed = VaccinationInfo::from_str(LINE)?;
+
+                check!(parsed.to_record() == LINE);
+
+                Ok(())
+            }
+
+            fn date() -> impl Strategy<Value = NaiveDate> {
+                // dates in the wire format always have a four digit year
+                (1000i32..=9999, 1u32..=366)
+                    .prop_filter_map("invalid day of year", |(y, d)| NaiveDate::from_yo_opt(y, d))
+            }
+
+            prop_compose! {
+                fn vaccination_info()(
+                    id in any::<u64>(),
+                    issue_date in date(),
+                    names in "[^;]*",
+                    first_surname_letter in "[^;]",
+                    short_birthdate in date(),
+                    certificate_expiration in date(),
+                    // a line ending would be taken for the end of the record
+                    vaccine_type in "[^;\r\n]*",
+                ) -> VaccinationInfo {
+                    VaccinationInfo {
+                        id,
+                        version: 1,
+                        issue_date,
+                        names,
+                        first_surname_letter: first_surname_letter.chars().next().unwrap(),
+                        short_birthdate: ShortBirthdate::new(
+                            short_birthdate.month(),
+                            short_birthdate.day(),
+                        )
+                        .unwrap(),
+                        certificate_expiration,
+                        vaccine_type,
+                    }
+                }
+            }
+
+            proptest! {
+                #[test]
+                fn round_trip(info in vaccination_info()) {
+                    prop_assert_eq!(VaccinationInfo::from_str(&info.to_record()), Ok(info));
+                }
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        mod serde {
+            use super::*;
+            use assert2::let_assert;
+
+            static LINE: &str = "123456;1;20-01-2021;Anna Kowalska;M;17-04;20-01-2022;321";
+
+            #[test]
+            fn serialize() -> Result<(), MalformedLine> {
+                let parsed = VaccinationInfo::from_str(LINE)?;
+
+                let_assert!(Ok(json) = serde_json::to_value(&parsed));
+                check!(
+                    json == serde_json::json!({
+                        "id": 123456,
+                        "version": 1,
+                        "issue_date": "2021-01-20",
+                        "names": "Anna Kowalska",
+                        "first_surname_letter": "M",
+                        "short_birthdate": { "month": 4, "day": 17 },
+                        "certificate_expiration": "2022-01-20",
+                        "vaccine_type": "321",
+                    })
+                );
+
+                Ok(())
+            }
+
+            #[test]
+            fn round_trip() -> Result<(), MalformedLine> {
+                let parsed = VaccinationInfo::from_str(LINE)?;
+
+                let_assert!(Ok(json) = serde_json::to_string(&parsed));
+                let_assert!(Ok(deserialized) = serde_json::from_str::<VaccinationInfo>(&json));
+                check!(deserialized == parsed);
+
+                Ok(())
+            }
+
+            #[test]
+            fn invalid_fields() -> Result<(), MalformedLine> {
+                let parsed = VaccinationInfo::from_str(LINE)?;
+                let_assert!(Ok(json) = serde_json::to_value(&parsed));
+
+                for (key, value, field) in &[
+                    ("version", serde_json::json!(2), FieldName::Version),
+                    ("names", serde_json::json!("Anna;Maria"), FieldName::Names),
+                    (
+                        "first_surname_letter",
+                        serde_json::json!(";"),
+                        FieldName::FirstSurnameLetter,
+                    ),
+                    (
+                        "vaccine_type",
+                        serde_json::json!("3;21"),
+                        FieldName::VaccineType,
+                    ),
+                    (
+                        "vaccine_type",
+                        serde_json::json!("321\n"),
+                        FieldName::VaccineType,
+                    ),
+                ] {
+                    let mut json = json.clone();
+                    json[key] = value.clone();
+
+                    let_assert!(Err(e) = serde_json::from_value::<VaccinationInfo>(json));
+                    check!(e.to_string() == MalformedLine::MalformedFieldData(*field).to_string());
+                }
+
+                Ok(())
+            }
+
+            #[test]
+            fn invalid_short_birthdate() {
+                let_assert!(
+                    Err(_) = serde_json::from_str::<ShortBirthdate>(r#"{"month": 2, "day": 30}"#)
+                );
+            }
+
+            #[test]
+            fn error() {
+                let e = MalformedLine::MissingField(FieldName::ShortBirthdate);
+
+                let_assert!(Ok(json) = serde_json::to_value(&e));
+                check!(
+                    json == serde_json::json!({ "kind": "missing_field", "field": "ShortBirthdate" })
+                );
+                let_assert!(Ok(deserialized) = serde_json::from_value::<MalformedLine>(json));
+                check!(deserialized == e);
+            }
+        }
+
         mod malformed {
             use super::*;
             use assert2::let_assert;
@@ -161,6 +596,15 @@ mod tests {
                 let_assert!(Err(e) = VaccinationInfo::from_str("123;1;12-21-42"));
                 check!(e == MalformedLine::MalformedFieldData(FieldName::IssueDate));
             }
+
+            #[test]
+            fn short_birthdate() {
+                let_assert!(
+                    Err(e) =
+                        VaccinationInfo::from_str("123;1;20-01-2021;Anna;M;31-04;20-01-2022;321")
+                );
+                check!(e == MalformedLine::MalformedFieldData(FieldName::ShortBirthdate));
+            }
         }
     }
 }
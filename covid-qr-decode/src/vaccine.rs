@@ -0,0 +1,225 @@
+use displaythis::Display;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Vaccine product, as told by the `danaTechniczna` code
+///
+/// The built-in codes are the EU marketing authorisation numbers, e.g. `EU/1/20/1528`.
+/// There is no published list of the ministry's own codes, such as `321`, so those are
+/// [`Unknown`](Self::Unknown); use a [`VaccineRegistry`] to describe them.
+#[derive(Display, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum VaccineType {
+    #[display("Comirnaty")]
+    Comirnaty,
+    #[display("Spikevax")]
+    Spikevax,
+    #[display("Vaxzevria")]
+    Vaxzevria,
+    #[display("Jcovden")]
+    Jcovden,
+    #[display("Nuvaxovid")]
+    Nuvaxovid,
+    /// Code not in the built-in table
+    #[display("unknown vaccine {0}")]
+    Unknown(String),
+}
+
+struct Builtin {
+    code: &'static str,
+    vaccine_type: VaccineType,
+    manufacturer: &'static str,
+    doses: u8,
+    dose_interval_days: Option<u32>,
+}
+
+// Codes are the numbers in the EU Union Register of medicinal products; doses and intervals are
+// the primary course from each product's information
+static BUILTIN: &[Builtin] = &[
+    // Comirnaty, Union Register EU/1/20/1528
+    Builtin {
+        code: "EU/1/20/1528",
+        vaccine_type: VaccineType::Comirnaty,
+        manufacturer: "BioNTech/Pfizer",
+        doses: 2,
+        dose_interval_days: Some(21),
+    },
+    // Spikevax, Union Register EU/1/20/1507
+    Builtin {
+        code: "EU/1/20/1507",
+        vaccine_type: VaccineType::Spikevax,
+        manufacturer: "Moderna",
+        doses: 2,
+        dose_interval_days: Some(28),
+    },
+    // Vaxzevria, Union Register EU/1/21/1529
+    Builtin {
+        code: "EU/1/21/1529",
+        vaccine_type: VaccineType::Vaxzevria,
+        manufacturer: "AstraZeneca",
+        doses: 2,
+        dose_interval_days: Some(28),
+    },
+    // Jcovden, Union Register EU/1/20/1525
+    Builtin {
+        code: "EU/1/20/1525",
+        vaccine_type: VaccineType::Jcovden,
+        manufacturer: "Janssen",
+        doses: 1,
+        dose_interval_days: None,
+    },
+    // Nuvaxovid, Union Register EU/1/21/1618
+    Builtin {
+        code: "EU/1/21/1618",
+        vaccine_type: VaccineType::Nuvaxovid,
+        manufacturer: "Novavax",
+        doses: 2,
+        dose_interval_days: Some(21),
+    },
+];
+
+impl VaccineType {
+    pub fn from_code(code: &str) -> Self {
+        BUILTIN
+            .iter()
+            .find(|builtin| builtin.code == code)
+            .map(|builtin| builtin.vaccine_type.clone())
+            .unwrap_or_else(|| VaccineType::Unknown(code.to_owned()))
+    }
+
+    /// Built-in details, `None` for unknown codes
+    pub fn vaccine(&self) -> Option<Vaccine> {
+        BUILTIN
+            .iter()
+            .find(|builtin| builtin.vaccine_type == *self)
+            .map(|builtin| Vaccine {
+                manufacturer: builtin.manufacturer.to_owned(),
+                product: self.to_string(),
+                doses: builtin.doses,
+                dose_interval_days: builtin.dose_interval_days,
+            })
+    }
+}
+
+/// Vaccine details for staff checking certificates
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vaccine {
+    pub manufacturer: String,
+    pub product: String,
+    /// Doses in the primary course
+    pub doses: u8,
+    /// Minimum days between doses, for multi-dose courses
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub dose_interval_days: Option<u32>,
+}
+
+impl fmt::Display for Vaccine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}), ", self.product, self.manufacturer)?;
+
+        match (self.doses, self.dose_interval_days) {
+            (1, _) => f.write_str("1 dose"),
+            (doses, Some(days)) => write!(f, "{} doses, {} days apart", doses, days),
+            (doses, None) => write!(f, "{} doses", doses),
+        }
+    }
+}
+
+/// Maps `danaTechniczna` codes to vaccine details, on top of the built-in codes
+///
+/// With the `serde` feature it can be loaded from a map of codes to [`Vaccine`]s.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct VaccineRegistry {
+    overrides: HashMap<String, Vaccine>,
+}
+
+impl VaccineRegistry {
+    /// Registry with the built-in codes only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Describes a code, replacing the built-in details if there are any
+    pub fn insert(&mut self, code: impl Into<String>, vaccine: Vaccine) {
+        self.overrides.insert(code.into(), vaccine);
+    }
+
+    /// Adds every code of `other`, replacing the ones already described
+    pub fn extend(&mut self, other: VaccineRegistry) {
+        self.overrides.extend(other.overrides);
+    }
+
+    pub fn get(&self, code: &str) -> Option<Vaccine> {
+        self.overrides
+            .get(code)
+            .cloned()
+            .or_else(|| VaccineType::from_code(code).vaccine())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::{check, let_assert};
+
+    mod data {
+        use super::*;
+
+        pub fn vaccine() -> Vaccine {
+            Vaccine {
+                manufacturer: "Test".to_owned(),
+                product: "Testvax".to_owned(),
+                doses: 3,
+                dose_interval_days: None,
+            }
+        }
+    }
+
+    #[test]
+    fn builtin() {
+        check!(VaccineType::from_code("EU/1/20/1528") == VaccineType::Comirnaty);
+        check!(VaccineType::from_code("321") == VaccineType::Unknown("321".to_owned()));
+
+        let_assert!(Some(vaccine) = VaccineType::Jcovden.vaccine());
+        check!(vaccine.doses == 1);
+        check!(VaccineType::Unknown("321".to_owned()).vaccine() == None);
+
+        check!(VaccineType::Spikevax.to_string() == "Spikevax");
+        check!(VaccineType::Unknown("321".to_owned()).to_string() == "unknown vaccine 321");
+    }
+
+    #[test]
+    fn overrides() {
+        let mut registry = VaccineRegistry::new();
+        check!(registry.get("321") == None);
+        check!(registry.get("EU/1/20/1528").map(|v| v.product) == Some("Comirnaty".to_owned()));
+
+        registry.insert("321", data::vaccine());
+        registry.insert("EU/1/20/1528", data::vaccine());
+
+        check!(registry.get("321") == Some(data::vaccine()));
+        check!(registry.get("EU/1/20/1528") == Some(data::vaccine()));
+        check!(registry.get("EU/1/20/1507").map(|v| v.product) == Some("Spikevax".to_owned()));
+    }
+
+    #[test]
+    fn display() {
+        check!(data::vaccine().to_string() == "Testvax (Test), 3 doses");
+
+        let_assert!(Some(vaccine) = VaccineType::Comirnaty.vaccine());
+        check!(vaccine.to_string() == "Comirnaty (BioNTech/Pfizer), 2 doses, 21 days apart");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize() {
+        let json = r#"{"321": {"manufacturer": "Test", "product": "Testvax", "doses": 3}}"#;
+
+        let_assert!(Ok(registry) = serde_json::from_str::<VaccineRegistry>(json));
+        check!(registry.get("321") == Some(data::vaccine()));
+    }
+}
@@ -0,0 +1,516 @@
+use crate::clock::{Clock, SystemClock};
+use crate::detect::{self, Candidate, CodeLocation, Confidence, Content, InputKind};
+use crate::error::{DetectError, KeyringError, MalformedLine};
+use crate::keyring::Keyring;
+use crate::policy::{Policy, PolicyResult};
+use crate::preprocess::Attempt;
+use crate::qr::QrDecoder;
+use crate::vaccination_info::VaccinationInfo;
+use chrono::NaiveDate;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Step of the verification pipeline, in order
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Stage {
+    /// Telling the input kind
+    Detection,
+    /// Reading the ciphertext from the PDF, image or payload
+    Extraction,
+    /// Checking the signature against the trusted keys
+    Signature,
+    /// Parsing the signed record
+    Record,
+    /// Every step succeeded
+    Complete,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Verdict {
+    Valid,
+    Expired,
+    /// Valid, but failed a rule of the [`Policy`]
+    Rejected,
+    /// Well-formed plaintext record, with no signature to vouch for it
+    Unsigned,
+    Invalid,
+}
+
+/// Failure of a single stage, see [`stage`](Self::stage)
+#[derive(Debug, Error)]
+pub enum VerificationError {
+    #[error("Unable to tell the input type")]
+    Detection(#[source] DetectError),
+    #[error("Unable to read the certificate")]
+    Extraction(#[source] DetectError),
+    #[error("No trusted key verified the signature")]
+    Signature(#[from] KeyringError),
+    #[error("Malformed record")]
+    Record(#[from] MalformedLine),
+}
+
+impl VerificationError {
+    pub fn stage(&self) -> Stage {
+        match self {
+            VerificationError::Detection(_) => Stage::Detection,
+            VerificationError::Extraction(_) => Stage::Extraction,
+            VerificationError::Signature(_) => Stage::Signature,
+            VerificationError::Record(_) => Stage::Record,
+        }
+    }
+
+    /// Stable error code, as in [`Error::code`](crate::error::Error::code)
+    pub fn code(&self) -> &'static str {
+        match self {
+            VerificationError::Detection(e) | VerificationError::Extraction(e) => e.code(),
+            VerificationError::Signature(e) => e.code(),
+            VerificationError::Record(e) => e.code(),
+        }
+    }
+}
+
+/// Outcome of verifying one certificate
+#[derive(Debug)]
+pub struct VerificationResult {
+    /// Input kind, as given or detected; `None` if detection failed
+    pub kind: Option<InputKind>,
+    /// How sure the detection was, if the kind wasn't given
+    pub confidence: Option<Confidence>,
+    /// Where the QR code was found, for PDFs and images
+    pub location: Option<CodeLocation>,
+    /// Every QR code detection pass on the input's images, in order
+    pub attempts: Vec<Attempt>,
+    /// Stage that failed, or [`Stage::Complete`]
+    pub stage: Stage,
+    pub verdict: Verdict,
+    /// Signed payload, if it was read; plaintext records have none
+    pub ciphertext: Option<Vec<u8>>,
+    pub record: Option<VaccinationInfo>,
+    /// Name of the key that matched the signature
+    pub key: Option<String>,
+    /// Outcome of the policy rules, if a policy was set and the record was read
+    pub policy: Option<PolicyResult>,
+    pub error: Option<VerificationError>,
+}
+
+impl VerificationResult {
+    fn new(kind: Option<InputKind>, confidence: Option<Confidence>) -> Self {
+        VerificationResult {
+            kind,
+            confidence,
+            location: None,
+            attempts: Vec::new(),
+            stage: Stage::Detection,
+            verdict: Verdict::Invalid,
+            ciphertext: None,
+            record: None,
+            key: None,
+            policy: None,
+            error: None,
+        }
+    }
+
+    fn failed(mut self, error: VerificationError) -> Self {
+        self.stage = error.stage();
+        self.verdict = Verdict::Invalid;
+        self.error = Some(error);
+        self
+    }
+
+    fn parsed(
+        mut self,
+        record: Result<VaccinationInfo, MalformedLine>,
+        today: NaiveDate,
+        policy: Option<&Policy>,
+    ) -> Self {
+        match record {
+            Ok(record) => {
+                self.stage = Stage::Complete;
+                self.policy = policy.map(|policy| policy.check(&record, today));
+                // only a signature matching a trusted key makes a record valid
+                self.verdict = if self.key.is_none() {
+                    Verdict::Unsigned
+                } else if record.has_expired_on(today) {
+                    Verdict::Expired
+                } else if self.policy.as_ref().is_some_and(|policy| !policy.passed()) {
+                    Verdict::Rejected
+                } else {
+                    Verdict::Valid
+                };
+                self.record = Some(record);
+                self
+            }
+            Err(e) => self.failed(e.into()),
+        }
+    }
+
+    /// Whether every stage succeeded; the certificate may still be unsigned, expired or rejected
+    pub fn is_verified(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Runs the whole pipeline, from any supported input to a verified record
+///
+/// ```no_run
+/// use covid_qr_decode::{Keyring, Verdict, Verifier};
+///
+/// let mut verifier = Verifier::new(Keyring::with_default_key());
+/// let result = verifier.verify(&std::fs::read("potwierdzenieQrSzczepienia.pdf")?);
+///
+/// if result.verdict == Verdict::Valid {
+///     println!("{:?}", result.record);
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Verifier {
+    keyring: Keyring,
+    qr: QrDecoder,
+    kind: Option<InputKind>,
+    clock: Box<dyn Clock + Send + Sync>,
+    policy: Option<Policy>,
+}
+
+impl Default for Verifier {
+    fn default() -> Self {
+        Self::new(Keyring::with_default_key())
+    }
+}
+
+impl Verifier {
+    pub fn new(keyring: Keyring) -> Self {
+        Verifier {
+            keyring,
+            qr: QrDecoder::new(),
+            kind: None,
+            clock: Box::new(SystemClock),
+            policy: None,
+        }
+    }
+
+    /// Decoder for the QR codes in PDFs and images, e.g. with preprocessing enabled
+    pub fn qr_decoder(mut self, qr: QrDecoder) -> Self {
+        self.qr = qr;
+        self
+    }
+
+    /// Skips the detection, treating every input as this kind
+    pub fn input_kind(mut self, kind: InputKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Clock telling the date the certificates must be valid on
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Rules the certificates must pass on top of the expiration date
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Verifies the QR codes in the input until one is valid
+    ///
+    /// Returns the first verified one, e.g. expired, if none is valid, and the first failure
+    /// if none is verified.
+    pub fn verify(&mut self, data: &[u8]) -> VerificationResult {
+        let mut verified = None;
+        let mut failed = None;
+
+        for result in self.results(data) {
+            if result.verdict == Verdict::Valid {
+                return result;
+            }
+
+            if result.is_verified() {
+                verified.get_or_insert(result);
+            } else {
+                failed.get_or_insert(result);
+            }
+        }
+
+        verified.or(failed).expect("there is at least one result")
+    }
+
+    /// Verifies every QR code in the input, e.g. all the certificates in a family's PDF
+    pub fn verify_all(&mut self, data: &[u8]) -> Vec<VerificationResult> {
+        self.results(data).collect()
+    }
+
+    fn results<'a>(&'a mut self, data: &[u8]) -> Box<dyn Iterator<Item = VerificationResult> + 'a> {
+        let (kind, confidence) = match self.kind {
+            Some(kind) => (kind, None),
+            None => match detect::detect(data) {
+                Ok(detection) => (detection.kind, Some(detection.confidence)),
+                Err(e) => {
+                    let result = VerificationResult::new(None, None);
+
+                    return Box::new(std::iter::once(
+                        result.failed(VerificationError::Detection(e)),
+                    ));
+                }
+            },
+        };
+
+        let mut attempts = Vec::new();
+        let content = detect::decode_recording(kind, data, &mut self.qr, &mut attempts);
+
+        let new_result = move || VerificationResult {
+            attempts: attempts.clone(),
+            ..VerificationResult::new(Some(kind), confidence)
+        };
+        let result = new_result();
+        let today = self.clock.today();
+        let policy = self.policy.as_ref();
+
+        match content {
+            Ok(Content::Record(record)) => Box::new(std::iter::once(result.parsed(
+                VaccinationInfo::from_str(&record),
+                today,
+                policy,
+            ))),
+            Ok(Content::Ciphertext(candidates)) => {
+                let keyring = &self.keyring;
+
+                Box::new(candidates.into_iter().map(move |candidate| {
+                    Self::verify_candidate(keyring, candidate, new_result(), today, policy)
+                }))
+            }
+            Err(e) => Box::new(std::iter::once(
+                result.failed(VerificationError::Extraction(e)),
+            )),
+        }
+    }
+
+    fn verify_candidate(
+        keyring: &Keyring,
+        candidate: Candidate,
+        mut result: VerificationResult,
+        today: NaiveDate,
+        policy: Option<&Policy>,
+    ) -> VerificationResult {
+        result.location = candidate.location;
+
+        let ciphertext = match candidate.ciphertext {
+            Ok(ciphertext) => ciphertext,
+            Err(e) => return result.failed(VerificationError::Extraction(e)),
+        };
+
+        let verified = keyring.decrypt(&ciphertext);
+        result.ciphertext = Some(ciphertext);
+
+        match verified {
+            Ok(verified) => {
+                result.key = Some(verified.key);
+                result.parsed(
+                    VaccinationInfo::from_str(&verified.plaintext),
+                    today,
+                    policy,
+                )
+            }
+            Err(e) => result.failed(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{PdfError, QrError};
+    use crate::{FixedClock, PdfQrGenerator, RsaDecrypter, Rule, TrustedKey};
+    use assert2::{check, let_assert};
+
+    mod data {
+        use super::*;
+
+        pub fn read(name: &str) -> Vec<u8> {
+            std::fs::read(format!("testdata/{}", name)).expect("Unable to read test data")
+        }
+
+        pub fn verifier() -> Verifier {
+            let pem = include_bytes!("../keys/test_public.pem");
+
+            let mut keyring = Keyring::new();
+            keyring.add(TrustedKey::new(
+                "test",
+                RsaDecrypter::from_pem(&pem[..]).expect("Malformed test RSA key"),
+            ));
+
+            Verifier::new(keyring)
+        }
+    }
+
+    #[test]
+    fn every_input_kind() {
+        let mut verifier = data::verifier();
+
+        for name in &["1.pdf", "1.png", "1.payload", "1.cipher"] {
+            let result = verifier.verify(&data::read(name));
+
+            check!(result.is_verified(), "{}: {:?}", name, result.error);
+            check!(result.stage == Stage::Complete);
+            // the test certificates are long expired
+            check!(result.verdict == Verdict::Expired);
+            check!(result.key.as_deref() == Some("test"));
+            // only images, including those in PDFs, are scanned
+            check!(
+                result.attempts.is_empty()
+                    == (name.ends_with("payload") || name.ends_with("cipher"))
+            );
+            let_assert!(Some(record) = result.record);
+            check!(record.to_record() == include_str!("../testdata/1.plain").trim_end());
+        }
+    }
+
+    #[test]
+    fn clock() {
+        let expiration = NaiveDate::from_ymd(2022, 1, 20);
+        let cipher = data::read("1.cipher");
+
+        let result = data::verifier()
+            .clock(FixedClock::on(expiration))
+            .verify(&cipher);
+        check!(result.verdict == Verdict::Valid);
+
+        let result = data::verifier()
+            .clock(FixedClock::on(expiration.succ()))
+            .verify(&cipher);
+        check!(result.verdict == Verdict::Expired);
+    }
+
+    #[test]
+    fn policy() {
+        let policy = Policy::new().rule(Rule::MaxAge { days: 30 });
+        let verify = |date| {
+            data::verifier()
+                .clock(FixedClock::on(date))
+                .policy(policy.clone())
+                .verify(&data::read("1.cipher"))
+        };
+
+        let result = verify(NaiveDate::from_ymd(2021, 2, 1));
+        check!(result.verdict == Verdict::Valid);
+        let_assert!(Some(rules) = result.policy);
+        check!(rules.passed());
+
+        let result = verify(NaiveDate::from_ymd(2021, 3, 1));
+        check!(result.is_verified());
+        check!(result.verdict == Verdict::Rejected);
+        let_assert!(Some(rules) = result.policy);
+        check!(!rules.passed());
+    }
+
+    #[test]
+    fn plaintext_record() {
+        let result = data::verifier().verify(&data::read("1.plain"));
+
+        check!(result.stage == Stage::Complete);
+        check!(result.verdict == Verdict::Unsigned);
+        check!(result.kind == Some(InputKind::Record));
+        check!(result.key == None);
+        check!(result.ciphertext == None);
+        check!(result.record.is_some());
+    }
+
+    #[test]
+    fn detection_failure() {
+        let result = data::verifier().verify(b"hello");
+
+        check!(result.stage == Stage::Detection);
+        check!(result.verdict == Verdict::Invalid);
+        check!(result.kind == None);
+        let_assert!(Some(VerificationError::Detection(DetectError::Unrecognized)) = result.error);
+    }
+
+    #[test]
+    fn extraction_failure() {
+        let result = data::verifier().verify(&data::read("missing_qr.png"));
+
+        check!(result.stage == Stage::Extraction);
+        check!(result.kind == Some(InputKind::Image));
+        let_assert!([attempt] = &result.attempts[..]);
+        check!(attempt.located == 0);
+        let_assert!(
+            Some(VerificationError::Extraction(DetectError::Qr(
+                QrError::NoData
+            ))) = result.error
+        );
+
+        // forcing the wrong kind
+        let result = data::verifier()
+            .input_kind(InputKind::Pdf)
+            .verify(&data::read("1.png"));
+        check!(result.stage == Stage::Extraction);
+        check!(result.confidence == None);
+        let_assert!(
+            Some(VerificationError::Extraction(DetectError::Pdf(
+                PdfError::PdfProcessing(_)
+            ))) = result.error
+        );
+    }
+
+    #[test]
+    fn signature_failure() {
+        // signed with the test key, which the default keyring doesn't trust
+        let result = Verifier::default().verify(&data::read("1.cipher"));
+
+        check!(result.stage == Stage::Signature);
+        check!(result.ciphertext.is_some());
+        check!(result.record.is_none());
+        let_assert!(
+            Some(VerificationError::Signature(KeyringError::NoMatchingKey(_))) = result.error
+        );
+    }
+
+    #[test]
+    fn record_failure() {
+        let result = data::verifier().verify(b"123456;1;not a record");
+
+        check!(result.stage == Stage::Record);
+        let_assert!(Some(VerificationError::Record(_)) = result.error);
+    }
+
+    #[test]
+    fn valid_code_first() {
+        let images: Vec<_> = ["missing_qr.png", "1.png", "2.png"]
+            .iter()
+            .map(|name| image::open(format!("testdata/{}", name)).unwrap())
+            .collect();
+        let pdf = PdfQrGenerator::default().generate_pages(&images);
+        let verify = |date| data::verifier().clock(FixedClock::on(date)).verify(&pdf);
+
+        // only the second certificate is still valid
+        let result = verify(NaiveDate::from_ymd(2022, 2, 1));
+        check!(result.verdict == Verdict::Valid);
+        check!(result.location.as_ref().and_then(|l| l.page) == Some(3));
+
+        // both expired
+        let result = verify(NaiveDate::from_ymd(2022, 4, 1));
+        check!(result.verdict == Verdict::Expired);
+        check!(result.location.as_ref().and_then(|l| l.page) == Some(2));
+    }
+
+    #[test]
+    fn all_codes() {
+        let images: Vec<_> = ["1.png", "missing_qr.png", "2.png"]
+            .iter()
+            .map(|name| image::open(format!("testdata/{}", name)).unwrap())
+            .collect();
+        let pdf = PdfQrGenerator::default().generate_pages(&images);
+
+        let results = data::verifier().verify_all(&pdf);
+
+        let_assert!([first, second] = &results[..]);
+        check!(first.is_verified());
+        check!(second.is_verified());
+        check!(first.location.as_ref().and_then(|l| l.page) == Some(1));
+        check!(second.location.as_ref().and_then(|l| l.page) == Some(3));
+    }
+}
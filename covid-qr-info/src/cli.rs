@@ -1,17 +1,55 @@
 use clap::{
-    crate_authors, crate_description, crate_name, crate_version, App, Arg, ArgGroup, ArgMatches,
+    crate_authors, crate_description, crate_name, crate_version, App, AppSettings, Arg, ArgGroup,
+    ArgMatches, SubCommand,
 };
 
+use crate::exit::EXIT_CODES_HELP;
+
+/// `--qr*` arguments; binary formats need `--qr-file` if the report goes to stdout too
+fn render_args(with_report: bool) -> [Arg<'static, 'static>; 4] {
+    let qr_file = Arg::with_name("qr-file")
+        .long("qr-file")
+        .help("write the rendered QR code to a file instead of stdout")
+        .takes_value(true)
+        .requires("qr");
+
+    [
+        Arg::with_name("qr")
+            .long("qr")
+            .help("render the QR code payload")
+            .takes_value(true)
+            .possible_values(&["png", "svg", "pdf", "terminal"]),
+        if with_report {
+            qr_file.required_ifs(&[("qr", "png"), ("qr", "pdf")])
+        } else {
+            qr_file
+        },
+        Arg::with_name("qr-ec")
+            .long("qr-ec")
+            .help("QR code error correction level")
+            .takes_value(true)
+            .possible_values(&["L", "M", "Q", "H"])
+            .default_value("M"),
+        Arg::with_name("qr-module-size")
+            .long("qr-module-size")
+            .help("QR code module size in pixels")
+            .takes_value(true)
+            .default_value("8"),
+    ]
+}
+
 pub(super) fn get_matches() -> ArgMatches<'static> {
     App::new(crate_name!())
         .version(crate_version!())
         .author(crate_authors!())
         .about(crate_description!())
+        .after_help(EXIT_CODES_HELP)
+        .setting(AppSettings::SubcommandsNegateReqs)
         .args(
             &[
                 Arg::with_name("auto")
                     .index(1)
-                    .help("autodetect file type")
+                    .help("autodetect file type; `-` reads from standard input")
                     .takes_value(true),
                 Arg::with_name("pdf")
                     .short("p")
@@ -38,12 +76,86 @@ pub(super) fn get_matches() -> ArgMatches<'static> {
                     .long("plaintext")
                     .help("read plaintext record")
                     .takes_value(true),
+                Arg::with_name("key")
+                    .short("k")
+                    .long("key")
+                    .help("trust RSA public key in PEM format; may be repeated")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1),
+                Arg::with_name("keyring")
+                    .long("keyring")
+                    .help("trust keys listed in a JSON keyring file")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1),
+                Arg::with_name("policy")
+                    .long("policy")
+                    .help("reject certificates failing the rules in a TOML or JSON policy file")
+                    .takes_value(true),
+                Arg::with_name("vaccines")
+                    .long("vaccines")
+                    .help("describe vaccine codes from a TOML or JSON file, on top of the built-in ones")
+                    .takes_value(true),
+                Arg::with_name("holder-name")
+                    .long("holder-name")
+                    .help("compare with the full name on the holder's ID document")
+                    .takes_value(true)
+                    .requires("holder-birthdate"),
+                Arg::with_name("holder-birthdate")
+                    .long("holder-birthdate")
+                    .help("compare with the birthdate (YYYY-MM-DD) on the holder's ID document")
+                    .takes_value(true)
+                    .requires("holder-name"),
+                Arg::with_name("at")
+                    .long("at")
+                    .help("check validity as of this date (YYYY-MM-DD) instead of today")
+                    .takes_value(true),
+                Arg::with_name("all-codes")
+                    .long("all-codes")
+                    .help("verify and report every QR code found, not just the first valid one"),
+                Arg::with_name("qr-backend")
+                    .long("qr-backend")
+                    .help("QR code decoder; `all` tries each one compiled in until one succeeds")
+                    .takes_value(true)
+                    .default_value("quircs"),
+                Arg::with_name("preprocess").long("preprocess").help(
+                    "retry unreadable photos with contrast, thresholding, rotation and other fixes",
+                ),
+                Arg::with_name("output")
+                    .short("o")
+                    .long("output")
+                    .help("output format")
+                    .takes_value(true)
+                    .possible_values(&["text", "json", "ndjson", "yaml"])
+                    .default_value("text"),
             ][..],
         )
+        .args(&render_args(true))
         .group(
             ArgGroup::with_name("input_type")
                 .args(&["pdf", "image", "base64", "encrypted", "record", "auto"])
                 .required(true),
         )
+        .subcommand(
+            SubCommand::with_name("issue")
+                .about("sign a plaintext record and print the QR code payload")
+                .args(
+                    &[
+                        Arg::with_name("key")
+                            .short("k")
+                            .long("key")
+                            .help("RSA private key in PEM format")
+                            .takes_value(true)
+                            .required(true),
+                        Arg::with_name("record")
+                            .index(1)
+                            .help("plaintext record to sign")
+                            .takes_value(true)
+                            .required(true),
+                    ][..],
+                )
+                .args(&render_args(false)),
+        )
         .get_matches()
 }
@@ -0,0 +1,40 @@
+use crate::error::{ConfigError, Failure};
+use clap::ArgMatches;
+use covid_qr_decode::{Policy, VaccineRegistry};
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::path::Path;
+
+/// Reads a `.toml` file as TOML, anything else as JSON
+fn load<T: DeserializeOwned>(
+    path: &str,
+    malformed: impl FnOnce(ConfigError) -> Failure,
+) -> Result<T, Failure> {
+    let data = fs::read(path)?;
+
+    let value = if Path::new(path).extension().is_some_and(|ext| ext == "toml") {
+        toml::from_slice(&data).map_err(ConfigError::from)
+    } else {
+        serde_json::from_slice(&data).map_err(ConfigError::from)
+    };
+
+    value.map_err(malformed)
+}
+
+/// Policy from `--policy`
+pub fn policy(args: &ArgMatches) -> Result<Option<Policy>, Failure> {
+    args.value_of("policy")
+        .map(|path| load(path, Failure::PolicyFile))
+        .transpose()
+}
+
+/// Built-in vaccine codes, with the ones from `--vaccines` on top
+pub fn vaccines(args: &ArgMatches) -> Result<VaccineRegistry, Failure> {
+    let mut registry = VaccineRegistry::new();
+
+    if let Some(path) = args.value_of("vaccines") {
+        registry.extend(load(path, Failure::VaccinesFile)?);
+    }
+
+    Ok(registry)
+}
@@ -0,0 +1,88 @@
+use covid_qr_decode::error::{DecryptionError, Error, MalformedLine, RenderError, SigningError};
+use covid_qr_decode::VerificationError;
+use std::io;
+use thiserror::Error;
+
+/// Malformed TOML or JSON configuration file
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    #[error("{0}")]
+    Toml(#[from] toml::de::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum Failure {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Unable to find payload in the input")]
+    NoPayload,
+    #[error("Malformed public key {0}: {1}")]
+    KeyMalformed(String, DecryptionError),
+    #[error("Malformed keyring file: {0}")]
+    KeyringFile(#[from] serde_json::Error),
+    #[error("Malformed policy file: {0}")]
+    PolicyFile(ConfigError),
+    #[error("Malformed vaccine registry file: {0}")]
+    VaccinesFile(ConfigError),
+    #[error(transparent)]
+    Decode(#[from] Error),
+}
+
+impl From<VerificationError> for Failure {
+    fn from(e: VerificationError) -> Self {
+        Failure::Decode(e.into())
+    }
+}
+
+impl From<MalformedLine> for Failure {
+    fn from(e: MalformedLine) -> Self {
+        Failure::Decode(e.into())
+    }
+}
+
+impl From<SigningError> for Failure {
+    fn from(e: SigningError) -> Self {
+        Failure::Decode(e.into())
+    }
+}
+
+impl From<RenderError> for Failure {
+    fn from(e: RenderError) -> Self {
+        Failure::Decode(e.into())
+    }
+}
+
+impl Failure {
+    /// Stable, machine-readable error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            Failure::Io(_) => "IO_ERROR",
+            Failure::NoPayload => "NO_PAYLOAD",
+            Failure::KeyMalformed(..) => "KEY_MALFORMED",
+            Failure::KeyringFile(_) => "KEYRING_MALFORMED",
+            Failure::PolicyFile(_) => "POLICY_MALFORMED",
+            Failure::VaccinesFile(_) => "VACCINES_MALFORMED",
+            Failure::Decode(e) => e.code(),
+        }
+    }
+
+    /// Message followed by its causes, e.g. `Malformed record: missing input field: Id`
+    pub fn message(&self) -> String {
+        let mut message = self.to_string();
+        let mut source = std::error::Error::source(self);
+
+        while let Some(e) = source {
+            let cause = e.to_string();
+            // some errors already end with their source's message
+            if !message.ends_with(&cause) {
+                message.push_str(": ");
+                message.push_str(&cause);
+            }
+            source = e.source();
+        }
+
+        message
+    }
+}
@@ -0,0 +1,94 @@
+use crate::error::Failure;
+use crate::output::Decoded;
+use covid_qr_decode::error::{Error, PdfError, QrError};
+use covid_qr_decode::Verdict;
+
+/// Process exit codes; these are part of the CLI interface and must stay stable
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(i32)]
+pub enum ExitStatus {
+    Valid = 0,
+    // 1 is reserved for command line usage errors reported by clap
+    Expired = 2,
+    SignatureInvalid = 3,
+    QrNotFound = 4,
+    MalformedRecord = 5,
+    UnsupportedPayloadVersion = 6,
+    Io = 7,
+    MalformedInput = 8,
+    SigningFailed = 9,
+    RenderFailed = 10,
+    PolicyRejected = 11,
+    IdentityMismatch = 12,
+    Unsigned = 13,
+    Configuration = 14,
+}
+
+pub const EXIT_CODES_HELP: &str = "EXIT CODES:
+    0    valid certificate
+    1    command line usage error
+    2    expired certificate
+    3    invalid cryptographic signature or no trusted key
+    4    QR code not found
+    5    malformed record
+    6    unsupported payload version
+    7    I/O error
+    8    malformed or unsupported input
+    9    signing failed
+    10   QR code rendering failed
+    11   valid certificate rejected by the policy
+    12   valid certificate not matching the holder's ID document
+    13   unsigned plaintext record
+    14   malformed key, keyring, policy or vaccine file";
+
+impl ExitStatus {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+impl From<&Failure> for ExitStatus {
+    fn from(failure: &Failure) -> Self {
+        match failure {
+            Failure::Io(_) | Failure::Decode(Error::Pdf(PdfError::Io(_))) => ExitStatus::Io,
+            Failure::KeyMalformed(..)
+            | Failure::KeyringFile(_)
+            | Failure::PolicyFile(_)
+            | Failure::VaccinesFile(_) => ExitStatus::Configuration,
+            Failure::Decode(e) => match e {
+                Error::Keyring(_) => ExitStatus::SignatureInvalid,
+                Error::Pdf(PdfError::QrNotFound) | Error::Qr(QrError::NoData) => {
+                    ExitStatus::QrNotFound
+                }
+                Error::Record(_) => ExitStatus::MalformedRecord,
+                Error::Qr(QrError::UnknownPayloadVersion(_)) => {
+                    ExitStatus::UnsupportedPayloadVersion
+                }
+                Error::Signing(_) => ExitStatus::SigningFailed,
+                Error::Render(_) => ExitStatus::RenderFailed,
+                _ => ExitStatus::MalformedInput,
+            },
+            _ => ExitStatus::MalformedInput,
+        }
+    }
+}
+
+impl From<&Result<Decoded, Failure>> for ExitStatus {
+    fn from(result: &Result<Decoded, Failure>) -> Self {
+        match result {
+            Ok(decoded) => match decoded.verdict {
+                Verdict::Expired => ExitStatus::Expired,
+                Verdict::Rejected => ExitStatus::PolicyRejected,
+                Verdict::Unsigned => ExitStatus::Unsigned,
+                _ if decoded
+                    .identity
+                    .is_some_and(|identity| !identity.is_consistent()) =>
+                {
+                    ExitStatus::IdentityMismatch
+                }
+                _ => ExitStatus::Valid,
+            },
+            Err(failure) => failure.into(),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::error::Failure;
+use chrono::NaiveDate;
+use clap::ArgMatches;
+use covid_qr_decode::{Keyring, RsaDecrypter, TrustedKey};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// Keyring file entry; relative paths are resolved against the keyring file location
+#[derive(Debug, Deserialize)]
+struct KeyEntry {
+    name: String,
+    path: String,
+    valid_from: Option<NaiveDate>,
+    valid_until: Option<NaiveDate>,
+}
+
+fn load_key(name: &str, path: impl AsRef<Path>) -> Result<TrustedKey, Failure> {
+    let pem = fs::read(path)?;
+    let decrypter =
+        RsaDecrypter::from_pem(pem).map_err(|e| Failure::KeyMalformed(name.to_owned(), e))?;
+
+    Ok(TrustedKey::new(name, decrypter))
+}
+
+fn load_keyring_file(keyring: &mut Keyring, path: &str) -> Result<(), Failure> {
+    let data = fs::read(path)?;
+    let entries: Vec<KeyEntry> = serde_json::from_slice(&data)?;
+
+    let base = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+    for entry in entries {
+        let mut key = load_key(&entry.name, base.join(&entry.path))?;
+
+        if let Some(date) = entry.valid_from {
+            key = key.valid_from(date);
+        }
+        if let Some(date) = entry.valid_until {
+            key = key.valid_until(date);
+        }
+
+        keyring.add(key);
+    }
+
+    Ok(())
+}
+
+/// Keys from `--key` and `--keyring`, or the built-in ministry key if neither was given
+pub fn keyring(args: &ArgMatches) -> Result<Keyring, Failure> {
+    let mut keyring = Keyring::new();
+
+    for path in args.values_of("key").into_iter().flatten() {
+        keyring.add(load_key(path, path)?);
+    }
+
+    for path in args.values_of("keyring").into_iter().flatten() {
+        load_keyring_file(&mut keyring, path)?;
+    }
+
+    if keyring.is_empty() {
+        Ok(Keyring::with_default_key())
+    } else {
+        Ok(keyring)
+    }
+}
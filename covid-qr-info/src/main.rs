@@ -1,180 +1,265 @@
-use covid_qr_decode::{PdfQrExtractor, QrDecoder, RsaDecrypter, VaccinationInfo};
-use std::fs::{metadata, File};
+use chrono::NaiveDate;
+use clap::{value_t, ArgMatches};
+use covid_qr_decode::{
+    Confidence, FixedClock, IdentityDocument, InputKind, Preprocessing, QrBackend, QrDecoder,
+    RsaSigner, Stage, VaccinationInfo, VaccineRegistry, Verdict, VerificationResult, Verifier,
+};
+use std::fs::{self, File};
 use std::io::{self, BufReader, Read};
-use std::path::Path;
 use std::str::FromStr;
 
 mod cli;
+mod config;
+mod error;
+mod exit;
+mod keys;
+mod output;
+mod render;
+
+use error::Failure;
+use exit::ExitStatus;
+use output::{AttemptReport, Decoded, Location, OutputFormat, Report};
 
 type Payload = Vec<u8>;
 
-const ENCRYPTED_PAYLOAD_LEN: u64 = 256;
+/// Path that stands for the standard input
+const STDIN: &str = "-";
 
-#[inline]
-fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
-    let file = File::open(path)?;
-    let mut reader = BufReader::new(file);
-    let mut data = String::new();
+fn read_bytes(path: &str) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
 
-    reader.read_to_string(&mut data)?;
+    if path == STDIN {
+        io::stdin().lock().read_to_end(&mut data)?;
+    } else {
+        BufReader::new(File::open(path)?).read_to_end(&mut data)?;
+    }
 
     Ok(data)
 }
 
-fn main() -> anyhow::Result<()> {
-    let args = cli::get_matches();
+#[inline]
+fn read_to_string(path: &str) -> io::Result<String> {
+    String::from_utf8(read_bytes(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
 
-    let (auto_pdf, auto_qr, auto_base64, auto_encrypted, auto_record) = match args.value_of("auto")
-    {
-        Some(path) => {
-            let (mut auto_pdf, mut auto_qr, mut auto_base64, mut auto_encrypted, mut auto_record) =
-                (None, None, None, None, None);
-
-            match tree_magic_mini::from_filepath(path.as_ref()) {
-                Some(pdf) if pdf == "application/pdf" => auto_pdf = Some(path),
-                Some(image) if image.starts_with("image/") => auto_qr = Some(path),
-                Some(text) if text == "text/plain" => {
-                    // binary ciphertext will also be recognized as text/plain
-                    // so try a file size heuristic
-                    match metadata(path) {
-                        Ok(meta) if meta.len() == ENCRYPTED_PAYLOAD_LEN => {
-                            auto_encrypted = Some(path);
-                        }
-                        Ok(_) => {
-                            // this can be base64 or record
-                            // check if there's a separator present
-                            // this will read the contents twice, but it's not a frequently used path anyway
-
-                            if let Ok(data) = read_to_string(path) {
-                                // base64 alphabet doesn't contain the separator
-                                // but the qr code text payload contains ';', which separates version
-                                // information and the base64-encoded payload itself
-
-                                if let Some((_, b)) = data.split_once(';') {
-                                    // at this point it's either 1;base64 or a record
-
-                                    if b.contains(';') {
-                                        auto_record = Some(path);
-                                    } else {
-                                        auto_base64 = Some(path);
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            // error at this point is fatal
-
-                            anyhow::bail!("Failed to read plaintext file: {}", e);
-                        }
-                    }
-                }
-                Some(mime) => {
-                    anyhow::bail!("Unable to process file type {}", mime);
-                }
-                None => {
-                    anyhow::bail!("Unable to process unknonw file type");
-                }
-            }
+/// Input type, unless it should be detected, and contents; `-` reads from the standard input
+fn select_input(args: &ArgMatches) -> Result<(Option<InputKind>, Vec<u8>), Failure> {
+    if let Some(path) = args.value_of("auto") {
+        return Ok((None, read_bytes(path)?));
+    }
+
+    let (kind, path) = [
+        ("pdf", InputKind::Pdf),
+        ("image", InputKind::Image),
+        ("base64", InputKind::Base64),
+        ("encrypted", InputKind::Encrypted),
+        ("record", InputKind::Record),
+    ]
+    .iter()
+    .find_map(|&(name, kind)| args.value_of(name).map(|path| (kind, path)))
+    .ok_or(Failure::NoPayload)?;
+
+    Ok((Some(kind), read_bytes(path)?))
+}
+
+/// Decrypted payload, or the plaintext record itself, with its verification result
+struct Outcome {
+    source: Option<InputKind>,
+    confidence: Option<Confidence>,
+    location: Option<Location>,
+    attempts: Vec<AttemptReport>,
+    stage: Option<Stage>,
+    payload: Option<Payload>,
+    result: Result<Decoded, Failure>,
+}
 
-            (auto_pdf, auto_qr, auto_base64, auto_encrypted, auto_record)
+impl Outcome {
+    fn failed(failure: Failure) -> Self {
+        Outcome {
+            source: None,
+            confidence: None,
+            location: None,
+            attempts: Vec::new(),
+            stage: None,
+            payload: None,
+            result: Err(failure),
         }
-        None => (None, None, None, None, None),
-    };
+    }
+
+    /// Outcome of a verification, with the vaccine described by `vaccines`
+    /// and the record compared with the `holder`'s ID document
+    fn new(
+        verified: VerificationResult,
+        vaccines: &VaccineRegistry,
+        holder: Option<&IdentityDocument>,
+    ) -> Self {
+        let result = match (verified.error, verified.record) {
+            (Some(e), _) => Err(e.into()),
+            (None, Some(record)) => Ok(Decoded {
+                vaccine: vaccines.get(record.vaccine_type()),
+                identity: holder.map(|holder| holder.compare(&record)),
+                record,
+                key: verified.key,
+                verdict: verified.verdict,
+                policy: verified.policy,
+            }),
+            (None, None) => unreachable!("verified results carry the record"),
+        };
+
+        Outcome {
+            source: verified.kind,
+            confidence: verified.confidence,
+            location: verified.location.as_ref().map(Location::from),
+            attempts: verified.attempts.iter().map(AttemptReport::from).collect(),
+            stage: Some(verified.stage),
+            payload: verified.ciphertext,
+            result,
+        }
+    }
+}
 
-    let pdf_payload = auto_pdf
-        .or(args.value_of("pdf"))
-        .map(|path| -> anyhow::Result<Payload> {
-            let pdf = PdfQrExtractor::with_path(path)?;
-
-            let mut qr = QrDecoder::new();
-
-            let payload = pdf
-                .images()
-                .filter_map(|image| image.ok())
-                .filter_map(move |image| qr.image_extract_encrypted(image).ok())
-                .next()
-                .ok_or_else(|| anyhow::anyhow!("Unable to find QR code in the PDF file"))?;
-
-            Ok(payload)
-        });
-
-    let qr_payload = || {
-        auto_qr
-            .or(args.value_of("image"))
-            .map(|path| -> anyhow::Result<Payload> {
-                let mut qr = QrDecoder::new();
-
-                Ok(qr.read_image(path).map_err(|e| {
-                    anyhow::anyhow!("Unable to find QR code in the PDF file, {}", e)
-                })?)
-            })
-    };
+/// The first valid outcome, else the first successfully verified one, else the first one
+fn best(outcomes: &[Outcome]) -> &Outcome {
+    let valid = |outcome: &&Outcome| matches!(&outcome.result, Ok(decoded) if decoded.verdict == Verdict::Valid);
 
-    let base64_payload = || {
-        auto_base64
-            .or(args.value_of("base64"))
-            .map(|path| -> anyhow::Result<Payload> {
-                let data = read_to_string(path)?;
+    outcomes
+        .iter()
+        .find(valid)
+        .or_else(|| outcomes.iter().find(|outcome| outcome.result.is_ok()))
+        .unwrap_or(&outcomes[0])
+}
 
-                // decode
-                let payload = QrDecoder::decode_payload(&data)?;
+fn exit_on_failure(result: Result<(), Failure>) {
+    if let Err(e) = result {
+        eprintln!("Error: {}", e.message());
 
-                Ok(payload)
-            })
-    };
+        std::process::exit(ExitStatus::from(&e).code());
+    }
+}
+
+fn issue(args: &ArgMatches) -> Result<(), Failure> {
+    // both are required by clap
+    let key = fs::read(args.value_of("key").unwrap())?;
+    let record = read_to_string(args.value_of("record").unwrap())?;
 
-    let ciphertext = || {
-        auto_encrypted
-            .or(args.value_of("encrypted"))
-            .map(|path| -> anyhow::Result<Payload> {
-                let file = File::open(path)?;
-                let mut reader = BufReader::new(file);
-                let mut data = Vec::new();
+    let info = VaccinationInfo::from_str(record.trim_end())?;
+    let signer = RsaSigner::from_pem(key)?;
 
-                reader.read_to_end(&mut data)?;
+    let payload = signer.sign_payload(&info)?;
 
-                Ok(data)
-            })
+    if args.is_present("qr") {
+        render::render(args, &payload)
+    } else {
+        println!("{}", payload);
+
+        Ok(())
+    }
+}
+
+fn main() {
+    let args = cli::get_matches();
+
+    if let Some(args) = args.subcommand_matches("issue") {
+        exit_on_failure(issue(args));
+
+        return;
+    }
+
+    let format = value_t!(args, "output", OutputFormat).unwrap_or_else(|e| e.exit());
+
+    let mut qr = match args.value_of("qr-backend") {
+        Some("all") => QrDecoder::all_backends(),
+        _ => QrDecoder::with_backend(
+            value_t!(args, "qr-backend", QrBackend).unwrap_or_else(|e| e.exit()),
+        ),
     };
+    if args.is_present("preprocess") {
+        qr = qr.preprocessing(Preprocessing::default());
+    }
 
-    let record = if let Some(payload) = pdf_payload
-        .or_else(qr_payload)
-        .or_else(base64_payload)
-        .or_else(ciphertext)
-    {
-        // decrypt & verify
-        let decrypter = RsaDecrypter::default();
-        let record = decrypter
-            .decrypt(payload?)
-            .map_err(|_e| anyhow::anyhow!("Invalid cryptographic signature"))?;
-
-        Ok(record)
+    let all = args.is_present("all-codes");
+    let at = if args.is_present("at") {
+        Some(value_t!(args, "at", NaiveDate).unwrap_or_else(|e| e.exit()))
     } else {
-        let record = auto_record
-            .or(args.value_of("record"))
-            .map(|path| -> anyhow::Result<String> {
-                let data = read_to_string(path)?;
+        None
+    };
+    // clap requires both
+    let holder = args.value_of("holder-name").map(|name| {
+        let birthdate = value_t!(args, "holder-birthdate", NaiveDate).unwrap_or_else(|e| e.exit());
+
+        IdentityDocument::from_full_name(name, birthdate)
+    });
+
+    let setup = select_input(&args).and_then(|input| {
+        Ok((
+            input,
+            keys::keyring(&args)?,
+            config::policy(&args)?,
+            config::vaccines(&args)?,
+        ))
+    });
+
+    let outcomes = match setup {
+        Ok(((kind, data), keyring, policy, vaccines)) => {
+            let mut verifier = Verifier::new(keyring).qr_decoder(qr);
+            if let Some(kind) = kind {
+                verifier = verifier.input_kind(kind);
+            }
+            if let Some(date) = at {
+                verifier = verifier.clock(FixedClock::on(date));
+            }
+            if let Some(policy) = policy {
+                verifier = verifier.policy(policy);
+            }
 
-                Ok(data)
-            })
-            .ok_or_else(|| anyhow::anyhow!("No payload found"))?;
+            let results = if all {
+                verifier.verify_all(&data)
+            } else {
+                vec![verifier.verify(&data)]
+            };
 
-        record
+            results
+                .into_iter()
+                .map(|result| Outcome::new(result, &vaccines, holder.as_ref()))
+                .collect()
+        }
+        Err(e) => vec![Outcome::failed(e)],
     };
 
-    let record = record?;
+    let reports: Vec<_> = outcomes
+        .iter()
+        .map(|outcome| {
+            Report::new(
+                outcome.source,
+                outcome.confidence,
+                outcome.location.clone(),
+                outcome.attempts.clone(),
+                outcome.stage,
+                &outcome.result,
+            )
+        })
+        .collect();
+
+    let printed = if all {
+        output::print_all(&reports, format)
+    } else {
+        output::print(&reports[0], format)
+    };
+    exit_on_failure(printed.map_err(Failure::from));
 
-    let record = VaccinationInfo::from_str(&record)?;
+    let outcome = best(&outcomes);
 
-    println!(
-        "{} vaccination certificate",
-        if record.has_expired() {
-            "Expired"
-        } else {
-            "Valid"
+    if args.is_present("qr") {
+        match (&outcome.payload, outcome.source) {
+            (Some(payload), _) => {
+                exit_on_failure(render::render(&args, &QrDecoder::encode_payload(payload)))
+            }
+            (None, Some(InputKind::Record)) => {
+                eprintln!("Warning: plaintext records have no QR code payload to render")
+            }
+            (None, _) => {}
         }
-    );
-    println!("{:#?}", record);
+    }
 
-    Ok(())
+    std::process::exit(ExitStatus::from(&outcome.result).code())
 }
@@ -0,0 +1,308 @@
+use crate::error::Failure;
+use covid_qr_decode::{
+    Attempt, CodeLocation, Confidence, FieldMatch, IdentityMatch, InputKind, Point, PolicyResult,
+    Stage, VaccinationInfo, Vaccine, Verdict,
+};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    NdJson,
+    Yaml,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::NdJson),
+            "yaml" => Ok(OutputFormat::Yaml),
+            other => Err(format!("unknown output format {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl From<&Failure> for ErrorReport {
+    fn from(failure: &Failure) -> Self {
+        ErrorReport {
+            code: failure.code(),
+            message: failure.message(),
+        }
+    }
+}
+
+/// Decoded record, with the name of the key that verified it (plaintext records aren't signed)
+#[derive(Debug)]
+pub struct Decoded {
+    pub record: VaccinationInfo,
+    pub key: Option<String>,
+    /// Valid, expired or rejected by the policy, as of the checked date, or unsigned
+    pub verdict: Verdict,
+    pub policy: Option<PolicyResult>,
+    /// Vaccine details, if the code is known
+    pub vaccine: Option<Vaccine>,
+    /// Comparison with the holder's ID document, if one was given
+    pub identity: Option<IdentityMatch>,
+}
+
+/// Where in the image the QR code was found
+#[derive(Debug, Clone, Serialize)]
+pub struct Location {
+    /// Page number, for PDFs
+    pub page: Option<u32>,
+    /// Name of the image on the page, for PDFs
+    pub image: Option<String>,
+    /// Missing for pages and images that failed to load
+    pub corners: Option<[Point; 4]>,
+    /// QR code decoder that read the code, missing for pages and images that failed to load
+    pub backend: Option<String>,
+    /// Preprocessing needed to read the code, empty if none was
+    pub preprocessing: Vec<String>,
+}
+
+impl From<&CodeLocation> for Location {
+    fn from(code: &CodeLocation) -> Self {
+        // only pages and images that failed to load have no backend
+        let scanned = !code.backend.is_empty();
+
+        Location {
+            page: code.page,
+            image: code.image.clone(),
+            corners: Some(code.corners).filter(|_| scanned),
+            backend: Some(code.backend.clone()).filter(|_| scanned),
+            preprocessing: code.preprocessing.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// QR code detection pass, as in [`Attempt`]
+#[derive(Debug, Clone, Serialize)]
+pub struct AttemptReport {
+    pub backend: String,
+    /// Preprocessing applied, empty for the image as is
+    pub preprocessing: Vec<String>,
+    pub located: usize,
+    pub decoded: usize,
+    /// Why the backend failed, if it did
+    pub error: Option<String>,
+}
+
+impl From<&Attempt> for AttemptReport {
+    fn from(attempt: &Attempt) -> Self {
+        AttemptReport {
+            backend: attempt.backend.clone(),
+            preprocessing: attempt.steps.iter().map(ToString::to_string).collect(),
+            located: attempt.located,
+            decoded: attempt.decoded,
+            error: attempt.error.clone(),
+        }
+    }
+}
+
+/// Output schema shared by all the machine-readable formats
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub verdict: Verdict,
+    pub source: Option<InputKind>,
+    /// How sure the input type detection was, when the type wasn't given
+    pub confidence: Option<Confidence>,
+    /// QR code location, when read from an image
+    pub location: Option<Location>,
+    /// Every QR code detection pass, with and without preprocessing, for images and PDFs
+    pub attempts: Vec<AttemptReport>,
+    /// Verification stage that failed, or `complete`; none if the input couldn't be read
+    pub stage: Option<Stage>,
+    pub key: Option<String>,
+    pub certificate: Option<VaccinationInfo>,
+    /// Vaccine the certificate's code stands for, if known
+    pub vaccine: Option<Vaccine>,
+    /// Comparison with `--holder-name` and `--holder-birthdate`
+    pub identity: Option<IdentityMatch>,
+    /// Outcome of each `--policy` rule
+    pub policy: Option<PolicyResult>,
+    pub error: Option<ErrorReport>,
+}
+
+impl Report {
+    pub fn new(
+        source: Option<InputKind>,
+        confidence: Option<Confidence>,
+        location: Option<Location>,
+        attempts: Vec<AttemptReport>,
+        stage: Option<Stage>,
+        result: &Result<Decoded, Failure>,
+    ) -> Self {
+        match result {
+            Ok(decoded) => Report {
+                verdict: decoded.verdict,
+                source,
+                confidence,
+                location,
+                attempts: attempts.clone(),
+                stage,
+                key: decoded.key.clone(),
+                certificate: Some(decoded.record.clone()),
+                vaccine: decoded.vaccine.clone(),
+                identity: decoded.identity,
+                policy: decoded.policy.clone(),
+                error: None,
+            },
+            Err(failure) => Report {
+                verdict: Verdict::Invalid,
+                source,
+                confidence,
+                location,
+                attempts,
+                stage,
+                key: None,
+                certificate: None,
+                vaccine: None,
+                identity: None,
+                policy: None,
+                error: Some(failure.into()),
+            },
+        }
+    }
+}
+
+fn field_match(field: FieldMatch) -> &'static str {
+    match field {
+        FieldMatch::Match => "match",
+        FieldMatch::Partial => "partial match",
+        FieldMatch::Mismatch => "mismatch",
+    }
+}
+
+fn write_text(out: &mut impl Write, report: &Report) -> io::Result<()> {
+    match (&report.certificate, &report.error) {
+        (Some(record), _) => {
+            writeln!(
+                out,
+                "{} vaccination certificate",
+                match report.verdict {
+                    Verdict::Expired => "Expired",
+                    Verdict::Rejected => "Rejected",
+                    Verdict::Unsigned => "Unsigned",
+                    _ => "Valid",
+                }
+            )?;
+            for rule in report.policy.iter().flat_map(PolicyResult::failures) {
+                writeln!(out, "Policy rule failed: {}", rule.reason)?;
+            }
+            if let Some(identity) = &report.identity {
+                writeln!(
+                    out,
+                    "Holder: given names {}, surname initial {}, birthdate {}",
+                    field_match(identity.given_names),
+                    field_match(identity.surname_initial),
+                    field_match(identity.birthdate)
+                )?;
+            }
+            if let Some(vaccine) = &report.vaccine {
+                writeln!(out, "Vaccine: {}", vaccine)?;
+            }
+            if let Some(key) = &report.key {
+                writeln!(out, "Signed with key {}", key)?;
+            }
+            if let Some(location) = report
+                .location
+                .as_ref()
+                .filter(|location| !location.preprocessing.is_empty())
+            {
+                writeln!(
+                    out,
+                    "QR code read after preprocessing: {}",
+                    location.preprocessing.join(", ")
+                )?;
+            }
+            writeln!(out, "{:#?}", record)?;
+        }
+        (None, Some(error)) => {
+            eprintln!("Error: {}", error.message);
+        }
+        (None, None) => {}
+    }
+
+    Ok(())
+}
+
+pub fn print(report: &Report, format: OutputFormat) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    match format {
+        OutputFormat::Text => write_text(&mut out, report)?,
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, report)?;
+            writeln!(out)?;
+        }
+        OutputFormat::NdJson => {
+            serde_json::to_writer(&mut out, report)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Yaml => {
+            serde_yaml::to_writer(&mut out, report).map_err(io::Error::other)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints one report per QR code; json and yaml get a single list, ndjson a line per code
+pub fn print_all(reports: &[Report], format: OutputFormat) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    match format {
+        OutputFormat::Text => {
+            for report in reports {
+                if let Some(location) = &report.location {
+                    match (location.corners, location.page, &location.image) {
+                        (Some([top_left, ..]), Some(page), Some(image)) => writeln!(
+                            out,
+                            "QR code at ({}, {}) of image {} on page {}",
+                            top_left.x, top_left.y, image, page
+                        )?,
+                        (Some([top_left, ..]), ..) => {
+                            writeln!(out, "QR code at ({}, {})", top_left.x, top_left.y)?
+                        }
+                        (None, Some(page), Some(image)) => {
+                            writeln!(out, "Image {} on page {}", image, page)?
+                        }
+                        (None, Some(page), None) => writeln!(out, "Page {}", page)?,
+                        (None, None, _) => {}
+                    }
+                }
+                write_text(&mut out, report)?;
+            }
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, reports)?;
+            writeln!(out)?;
+        }
+        OutputFormat::NdJson => {
+            for report in reports {
+                serde_json::to_writer(&mut out, report)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Yaml => {
+            serde_yaml::to_writer(&mut out, reports).map_err(io::Error::other)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use crate::error::Failure;
+use clap::{value_t, ArgMatches};
+use covid_qr_decode::{ErrorCorrection, PdfQrGenerator, QrRenderer};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// Renders the payload as requested by the `--qr*` arguments; does nothing without `--qr`
+pub fn render(args: &ArgMatches, payload: &str) -> Result<(), Failure> {
+    let format = match args.value_of("qr") {
+        Some(format) => format,
+        None => return Ok(()),
+    };
+
+    let ec = value_t!(args, "qr-ec", ErrorCorrection).unwrap_or_else(|e| e.exit());
+    let module_size = value_t!(args, "qr-module-size", u32).unwrap_or_else(|e| e.exit());
+
+    let renderer = QrRenderer::new(payload, ec)?.module_size(module_size);
+
+    let mut out: Box<dyn Write> = match args.value_of("qr-file") {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout()),
+    };
+
+    match format {
+        "png" => renderer.write_png(&mut out)?,
+        "svg" => writeln!(out, "{}", renderer.to_svg())?,
+        "pdf" => PdfQrGenerator::default().write(&renderer.to_image(), &mut out)?,
+        _ => writeln!(out, "{}", renderer.to_terminal())?,
+    }
+
+    out.flush()?;
+
+    Ok(())
+}